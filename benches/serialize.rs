@@ -0,0 +1,57 @@
+//! throughput of the serialization paths, `cargo bench`
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::io::Read;
+use std::path::PathBuf;
+use ureq_mime_multipart::MultipartBuilder;
+
+const FILE_LEN: usize = 16 << 20;
+
+/// a large file, written once per run
+fn synthetic_file() -> PathBuf {
+    let path = std::env::temp_dir().join("ureq-multipart-bench.bin");
+    let data: Vec<u8> = (0..FILE_LEN).map(|idx| (idx % 251) as u8).collect();
+    std::fs::write(&path, data).unwrap();
+    path
+}
+
+fn builder(path: &PathBuf) -> MultipartBuilder {
+    MultipartBuilder::new()
+        .add_text("name", "value")
+        .unwrap()
+        .add_file("file", path)
+        .unwrap()
+}
+
+fn serialize(c: &mut Criterion) {
+    let path = synthetic_file();
+    let mut group = c.benchmark_group("serialize");
+    group.throughput(Throughput::Bytes(FILE_LEN as u64));
+    group.sample_size(20);
+
+    group.bench_function("finish", |b| {
+        b.iter(|| builder(&path).finish().unwrap());
+    });
+    group.bench_function("build", |b| {
+        let builder = builder(&path);
+        b.iter(|| builder.build().unwrap());
+    });
+    group.bench_function("stream", |b| {
+        let mut buf = vec![0; 64 << 10];
+        b.iter(|| {
+            let (_, mut reader) = builder(&path).into_reader().unwrap();
+            let mut len = 0;
+            loop {
+                let read = reader.read(&mut buf).unwrap();
+                if read == 0 {
+                    break len;
+                }
+                len += read;
+            }
+        });
+    });
+    group.finish();
+    let _ = std::fs::remove_file(path);
+}
+
+criterion_group!(benches, serialize);
+criterion_main!(benches);
@@ -0,0 +1,88 @@
+use crate::{MultipartBuilder, MultipartError};
+use base64::alphabet::{self, Alphabet};
+use base64::engine::{general_purpose, DecodePaddingMode, Engine, GeneralPurpose};
+use mime::Mime;
+use std::io;
+
+/// the base64 alphabet of [`MultipartBuilder::add_base64`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Base64Alphabet {
+    /// RFC 4648 standard alphabet, with `+` and `/`
+    #[default]
+    Standard,
+    /// RFC 4648 url and filename safe alphabet, with `-` and `_`
+    UrlSafe,
+}
+
+impl Base64Alphabet {
+    fn alphabet(self) -> &'static Alphabet {
+        match self {
+            Base64Alphabet::Standard => &alphabet::STANDARD,
+            Base64Alphabet::UrlSafe => &alphabet::URL_SAFE,
+        }
+    }
+}
+
+impl MultipartBuilder {
+    /// add base64 encoded data, decoded right away
+    ///
+    /// * name field name
+    /// * filename optional file name
+    /// * content_type defaults to `application/octet-stream`
+    /// * b64 the encoded data, padded or not, whitespace is ignored
+    /// * alphabet the alphabet `b64` uses
+    pub fn add_base64(
+        self,
+        name: &str,
+        filename: Option<&str>,
+        content_type: Option<Mime>,
+        b64: &str,
+        alphabet: Base64Alphabet,
+    ) -> io::Result<Self> {
+        let engine = GeneralPurpose::new(
+            alphabet.alphabet(),
+            general_purpose::PAD.with_decode_padding_mode(DecodePaddingMode::Indifferent),
+        );
+        let b64: Vec<u8> = b64.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+        let data = engine
+            .decode(b64)
+            .map_err(|err| MultipartError::InvalidBase64 {
+                reason: err.to_string(),
+            })?;
+        self.add_bytes(name, filename, content_type, &data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Body;
+
+    fn decoded(b64: &str, alphabet: Base64Alphabet) -> io::Result<Vec<u8>> {
+        let builder = MultipartBuilder::new().add_base64("b", None, None, b64, alphabet)?;
+        match &builder.parts[0].body {
+            Body::Bytes(data) => Ok(data.clone()),
+            body => panic!("in memory part expected, got {body:?}"),
+        }
+    }
+
+    #[test]
+    fn test_add_base64() {
+        let data = b"\xfb\xff\xbfhi";
+        assert_eq!(decoded("+/+/aGk=", Base64Alphabet::Standard).unwrap(), data);
+        assert_eq!(
+            decoded("+/+/\naGk", Base64Alphabet::Standard).unwrap(),
+            data
+        );
+        assert_eq!(decoded("-_-_aGk", Base64Alphabet::UrlSafe).unwrap(), data);
+        assert_eq!(decoded("-_-_aGk=", Base64Alphabet::UrlSafe).unwrap(), data);
+
+        let err = decoded("-_-_aGk=", Base64Alphabet::Standard).unwrap_err();
+        let err = err.into_inner().unwrap().downcast::<MultipartError>();
+        assert!(matches!(
+            *err.unwrap(),
+            MultipartError::InvalidBase64 { .. }
+        ));
+        assert!(decoded("a", Base64Alphabet::Standard).is_err());
+    }
+}
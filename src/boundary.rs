@@ -0,0 +1,254 @@
+use crate::{check_boundary, random_boundary, MultipartBuilder, MultipartError};
+use std::fmt;
+use std::io;
+
+/// where the random part of boundaries comes from
+///
+/// [`RandomBoundary`] is used by default. Other sources are mostly meant for
+/// tests and fuzzing, e.g. to drive the collision handling with boundaries
+/// known to be in the data. Closures taking the alphabet and the length work
+/// as sources too.
+pub trait BoundarySource: Send {
+    /// the next random part, of `len` chars out of `alphabet`
+    ///
+    /// The result is validated, a source may return anything.
+    fn next_boundary(&mut self, alphabet: &[u8], len: usize) -> String;
+}
+
+/// random boundaries drawn from the thread rng
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomBoundary;
+
+impl BoundarySource for RandomBoundary {
+    fn next_boundary(&mut self, alphabet: &[u8], len: usize) -> String {
+        random_boundary(alphabet, len)
+    }
+}
+
+impl<F: FnMut(&[u8], usize) -> String + Send> BoundarySource for F {
+    fn next_boundary(&mut self, alphabet: &[u8], len: usize) -> String {
+        self(alphabet, len)
+    }
+}
+
+/// the builder's boundary source, which can't be debugged
+pub(crate) struct SourceBox(pub(crate) Box<dyn BoundarySource>);
+
+impl fmt::Debug for SourceBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BoundarySource")
+    }
+}
+
+impl MultipartBuilder {
+    /// draw the random part of boundaries from `source`
+    ///
+    /// The random part is regenerated right away with the same length, and
+    /// so are collisions later on. Fails when the source returns an invalid
+    /// boundary or one found in the data.
+    pub fn with_boundary_source<S: BoundarySource + 'static>(
+        mut self,
+        source: S,
+    ) -> io::Result<Self> {
+        self.boundary_source = SourceBox(Box::new(source));
+        self.boundary = self.next_boundary(self.boundary.len())?;
+        self.boundary_fixed = false;
+        self.boundary_from_names = false;
+        self.avoid_collisions()?;
+        Ok(self)
+    }
+    /// derive the random part of the boundary from the field names
+    ///
+    /// The boundary only depends on the names of the parts, in order, and on
+    /// the boundary shape: forms with the same fields get the same boundary,
+    /// e.g. for bodies used as idempotency keys. It's derived again as parts
+    /// are added, avoiding the data like random ones do, so call this after
+    /// the other boundary settings. A fixed boundary or a boundary source set
+    /// later replaces it.
+    pub fn with_deterministic_boundary_from_names(mut self) -> io::Result<Self> {
+        self.boundary_from_names = true;
+        self.boundary_fixed = false;
+        self.derive_boundary()?;
+        Ok(self)
+    }
+    /// derive the boundary from the names, retrying while the data holds it
+    pub(crate) fn derive_boundary(&mut self) -> io::Result<()> {
+        let len = self.boundary.len();
+        for attempt in 0..=self.max_boundary_attempts {
+            let boundary = names_boundary(
+                self.parts.iter().map(|part| part.name.as_str()),
+                attempt as u64,
+                &self.boundary_alphabet,
+                len,
+            );
+            check_boundary(&format!("{}{boundary}", self.boundary_prefix))?;
+            self.boundary = boundary;
+            if !self.collides(&self.parts) {
+                return Ok(());
+            }
+        }
+        // the first derived boundary counts too
+        Err(MultipartError::BoundaryCollision {
+            boundary: format!("{}{}", self.boundary_prefix, self.boundary),
+            attempts: self.max_boundary_attempts + 1,
+        }
+        .into())
+    }
+    /// a new random part of `len` chars, checked with the prefix
+    pub(crate) fn next_boundary(&mut self, len: usize) -> io::Result<String> {
+        let boundary = self
+            .boundary_source
+            .0
+            .next_boundary(&self.boundary_alphabet, len);
+        check_boundary(&format!("{}{boundary}", self.boundary_prefix))?;
+        Ok(boundary)
+    }
+}
+
+/// `len` chars out of `alphabet` from an FNV-1a hash of the names, which is
+/// stable across platforms and releases
+fn names_boundary<'a>(
+    names: impl Iterator<Item = &'a str>,
+    attempt: u64,
+    alphabet: &[u8],
+    len: usize,
+) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    for name in names {
+        feed(name.as_bytes());
+        feed(&[0]);
+    }
+    feed(&attempt.to_le_bytes());
+    // splitmix64 steps stretch the hash to any length
+    let mut state = hash;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^= z >> 31;
+            alphabet[(z % alphabet.len() as u64) as usize] as char
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MultipartError;
+
+    /// replays fixed boundaries, like a fuzzer would
+    fn fixed(boundaries: &[&str]) -> impl BoundarySource {
+        let mut boundaries: Vec<String> = boundaries.iter().rev().map(|b| b.to_string()).collect();
+        move |_: &[u8], _: usize| boundaries.pop().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_boundary_source() {
+        let builder = MultipartBuilder::new()
+            .with_boundary_prefix("")
+            .unwrap()
+            .with_boundary_source(fixed(&["aa", "bb", "cc"]))
+            .unwrap();
+        assert_eq!(builder.content_type(), "multipart/form-data; boundary=aa");
+        // the data holds the first replacement too
+        let (content_type, data) = builder
+            .add_text("k", "--aa --bb")
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(content_type, "multipart/form-data; boundary=cc");
+        assert!(data.starts_with(b"--cc\r\n"));
+
+        // an exhausted source returns an empty, invalid boundary
+        let err = MultipartBuilder::new()
+            .with_boundary_prefix("")
+            .unwrap()
+            .with_boundary_source(fixed(&["aa"]))
+            .unwrap()
+            .add_text("k", "--aa")
+            .unwrap_err();
+        let err = err.into_inner().unwrap().downcast::<MultipartError>();
+        assert!(matches!(
+            *err.unwrap(),
+            MultipartError::InvalidBoundary { .. }
+        ));
+
+        let err = MultipartBuilder::new().with_boundary_source(fixed(&["a\r\n"]));
+        assert!(err.is_err());
+    }
+
+    fn form(values: [&str; 2]) -> MultipartBuilder {
+        MultipartBuilder::new()
+            .with_deterministic_boundary_from_names()
+            .unwrap()
+            .add_text("user", values[0])
+            .unwrap()
+            .add_text("token", values[1])
+            .unwrap()
+    }
+
+    #[test]
+    fn test_deterministic_boundary_from_names() {
+        let first = form(["alice", "1"]);
+        assert_eq!(first.content_type(), form(["bob", "2"]).content_type());
+        let empty = MultipartBuilder::new()
+            .with_deterministic_boundary_from_names()
+            .unwrap();
+        assert_ne!(first.content_type(), empty.content_type());
+        let reordered = MultipartBuilder::new()
+            .with_deterministic_boundary_from_names()
+            .unwrap()
+            .add_text("token", "1")
+            .unwrap()
+            .add_text("user", "alice")
+            .unwrap();
+        assert_ne!(first.content_type(), reordered.content_type());
+        assert_eq!(first.boundary.len(), empty.boundary.len());
+        assert!(first.boundary.bytes().all(|b| b.is_ascii_digit()));
+
+        // data holding the derived delimiter gets another, still stable, one
+        let delimiter = first.delimiter();
+        let colliding = form(["alice", &delimiter]);
+        assert_ne!(colliding.delimiter(), delimiter);
+        assert_eq!(
+            colliding.content_type(),
+            form(["bob", &delimiter]).content_type()
+        );
+        assert!(!delimiter.contains(&colliding.delimiter()));
+
+        // every derived boundary collides with a one char alphabet
+        let builder = MultipartBuilder::new()
+            .with_boundary_alphabet("a")
+            .unwrap()
+            .with_boundary_len(1)
+            .unwrap()
+            .with_max_boundary_attempts(2)
+            .with_deterministic_boundary_from_names()
+            .unwrap();
+        let delimiter = builder.delimiter();
+        let err = builder.add_text("k", &delimiter).unwrap_err();
+        let err = err.into_inner().unwrap().downcast::<MultipartError>();
+        assert!(matches!(
+            *err.unwrap(),
+            MultipartError::BoundaryCollision { attempts: 3, .. }
+        ));
+
+        // a fixed boundary wins
+        let fixed = MultipartBuilder::new()
+            .with_deterministic_boundary_from_names()
+            .unwrap()
+            .with_boundary("B")
+            .unwrap()
+            .add_text("user", "alice")
+            .unwrap();
+        assert_eq!(fixed.content_type(), "multipart/form-data; boundary=B");
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{MultipartBuilder, MultipartError};
+use mime::Mime;
+use std::io::{self, Read};
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+impl MultipartBuilder {
+    /// add the standard output of a command
+    ///
+    /// * name field name
+    /// * filename optional file name
+    /// * content_type defaults to `application/octet-stream`
+    /// * cmd the command to run, its stdin is closed and its stderr inherited
+    ///
+    /// The command is spawned right away, so spawning errors are reported
+    /// here, and its output is streamed as the body is produced. Once the
+    /// output ends the command is waited for, and a non-zero exit fails the
+    /// read of the body, after all the output was sent. If the body is
+    /// dropped before that, the command is killed. Like
+    /// [`add_reader`](Self::add_reader), the part can only be read once.
+    pub fn add_command_output(
+        self,
+        name: &str,
+        filename: Option<&str>,
+        content_type: Option<Mime>,
+        mut cmd: Command,
+    ) -> io::Result<Self> {
+        let program = cmd.get_program().to_string_lossy().into_owned();
+        let mut child = cmd.stdin(Stdio::null()).stdout(Stdio::piped()).spawn()?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let reader = CommandReader {
+            program,
+            child: Some(child),
+            stdout,
+        };
+        self.add_reader(name, filename, content_type, reader)
+    }
+}
+
+/// the output of a command, checking its exit status at the end
+struct CommandReader {
+    program: String,
+    /// taken once the command exited
+    child: Option<Child>,
+    stdout: ChildStdout,
+}
+
+impl Read for CommandReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.stdout.read(buf)?;
+        if read > 0 || buf.is_empty() {
+            return Ok(read);
+        }
+        if let Some(mut child) = self.child.take() {
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(MultipartError::CommandFailed {
+                    program: self.program.clone(),
+                    code: status.code(),
+                }
+                .into());
+            }
+        }
+        Ok(0)
+    }
+}
+
+impl Drop for CommandReader {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_command_output() {
+        let mut cmd = Command::new("echo");
+        cmd.arg("hello");
+        let (_, data) = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_command_output("out", Some("out.txt"), Some(mime::TEXT_PLAIN), cmd)
+            .unwrap()
+            .finish()
+            .unwrap();
+        let expected =
+            "--B\r\nContent-Disposition: form-data; name=\"out\"; filename=\"out.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\nhello\n\r\n--B--\r\n";
+        assert_eq!(String::from_utf8(data).unwrap(), expected);
+
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo partial; exit 3"]);
+        let err = MultipartBuilder::new()
+            .add_command_output("out", None, None, cmd)
+            .unwrap()
+            .finish()
+            .unwrap_err();
+        let err = err.into_inner().unwrap().downcast::<MultipartError>();
+        assert_eq!(
+            *err.unwrap(),
+            MultipartError::CommandFailed {
+                program: "sh".to_string(),
+                code: Some(3),
+            }
+        );
+
+        assert!(MultipartBuilder::new()
+            .add_command_output("out", None, None, Command::new("/nonexistent/cmd"))
+            .is_err());
+    }
+}
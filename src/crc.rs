@@ -0,0 +1,78 @@
+use crate::MultipartReader;
+
+impl MultipartReader {
+    /// compute the crc32 checksum of the data of each part as it's read
+    ///
+    /// The data is checksummed as sent, i.e. after its transfer encoding,
+    /// and streams are covered too since nothing is read twice.
+    pub fn with_crc32(mut self) -> Self {
+        self.crcs = Some(Vec::new());
+        self
+    }
+    /// crc32 checksum of the data of the first part called `name`
+    ///
+    /// `None` until the part was read to its end, or without
+    /// [`with_crc32`](Self::with_crc32).
+    pub fn part_crc32(&self, name: &str) -> Option<u32> {
+        self.crcs
+            .as_ref()?
+            .iter()
+            .find(|(part, _)| part == name)
+            .map(|(_, crc)| *crc)
+    }
+    /// checksum data read from the front segment, when it holds part data
+    pub(crate) fn update_crc(&mut self, data: &[u8]) {
+        if self.crcs.is_some() && matches!(self.part_names.front(), Some(Some(_))) {
+            self.crc_hasher
+                .get_or_insert_with(crc32fast::Hasher::new)
+                .update(data);
+        }
+    }
+    /// store the checksum of the front segment, which was read to its end
+    pub(crate) fn finish_crc(&mut self) {
+        let name = self.part_names.pop_front().flatten();
+        let hasher = self.crc_hasher.take().unwrap_or_default();
+        if let (Some(crcs), Some(name)) = (&mut self.crcs, name) {
+            crcs.push((name, hasher.finalize()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::MultipartBuilder;
+    use std::io::{self, Read};
+
+    #[test]
+    fn test_part_crc32() {
+        let (_, mut reader) = MultipartBuilder::new()
+            .add_text("check", "123456789")
+            .unwrap()
+            .add_file("file", "test-vector0.txt")
+            .unwrap()
+            .add_reader("stream", None, None, io::Cursor::new(b"123456789".to_vec()))
+            .unwrap()
+            .add_reader("empty", None, None, io::empty())
+            .unwrap()
+            .into_reader()
+            .unwrap();
+        reader = reader.with_crc32();
+        let mut buf = [0; 7];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(reader.part_crc32("check"), None);
+        io::copy(&mut reader, &mut io::sink()).unwrap();
+        assert_eq!(reader.part_crc32("check"), Some(0xcbf4_3926));
+        assert_eq!(reader.part_crc32("file"), Some(0x41bd_9d77));
+        assert_eq!(reader.part_crc32("stream"), Some(0xcbf4_3926));
+        assert_eq!(reader.part_crc32("empty"), Some(0));
+        assert_eq!(reader.part_crc32("missing"), None);
+
+        let (_, mut reader) = MultipartBuilder::new()
+            .add_text("check", "123456789")
+            .unwrap()
+            .into_reader()
+            .unwrap();
+        io::copy(&mut reader, &mut io::sink()).unwrap();
+        assert_eq!(reader.part_crc32("check"), None);
+    }
+}
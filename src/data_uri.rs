@@ -0,0 +1,126 @@
+use crate::{percent_decode, MultipartBuilder, MultipartError};
+use mime::Mime;
+use std::io;
+
+impl MultipartBuilder {
+    /// add the data of a `data:` URI
+    ///
+    /// * name field name
+    /// * filename optional file name
+    /// * data_uri `data:[<mime>][;base64],<data>`
+    ///
+    /// The content type is the URI's one, `text/plain` when it has none. The
+    /// data is percent-decoded, then base64-decoded when marked so, which
+    /// needs the `base64` feature.
+    pub fn add_data_uri(
+        self,
+        name: &str,
+        filename: Option<&str>,
+        data_uri: &str,
+    ) -> io::Result<Self> {
+        let (content_type, data) = parse_data_uri(data_uri)?;
+        self.add_bytes(name, filename, Some(content_type), &data)
+    }
+}
+
+fn invalid(reason: &str) -> io::Error {
+    MultipartError::InvalidDataUri {
+        reason: reason.to_string(),
+    }
+    .into()
+}
+
+fn parse_data_uri(data_uri: &str) -> io::Result<(Mime, Vec<u8>)> {
+    let rest = data_uri
+        .get(..5)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("data:"))
+        .map(|_| &data_uri[5..])
+        .ok_or_else(|| invalid("missing data: scheme"))?;
+    let (meta, data) = rest
+        .split_once(',')
+        .ok_or_else(|| invalid("missing comma"))?;
+    let (media_type, base64) = match meta.rsplit_once(';') {
+        Some((media_type, param)) if param.eq_ignore_ascii_case("base64") => (media_type, true),
+        _ => (meta, false),
+    };
+
+    let media_type = if media_type.is_empty() || media_type.starts_with(';') {
+        format!("text/plain{media_type}")
+    } else {
+        media_type.to_string()
+    };
+    let content_type = media_type
+        .parse()
+        .map_err(|_| invalid("invalid media type"))?;
+
+    let data = percent_decode(data);
+    let data = if base64 { decode_base64(&data)? } else { data };
+    Ok((content_type, data))
+}
+
+#[cfg(feature = "base64")]
+fn decode_base64(data: &[u8]) -> io::Result<Vec<u8>> {
+    use base64::engine::{general_purpose, DecodePaddingMode, Engine, GeneralPurpose};
+
+    const ENGINE: GeneralPurpose = GeneralPurpose::new(
+        &base64::alphabet::STANDARD,
+        general_purpose::PAD.with_decode_padding_mode(DecodePaddingMode::Indifferent),
+    );
+    ENGINE
+        .decode(data)
+        .map_err(|err| invalid(&format!("invalid base64: {err}")))
+}
+
+#[cfg(not(feature = "base64"))]
+fn decode_base64(_: &[u8]) -> io::Result<Vec<u8>> {
+    Err(invalid("base64 data needs the base64 feature"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_plain_data_uri() {
+        let (content_type, data) = parse_data_uri("data:,Hello%2C%20World").unwrap();
+        assert_eq!(content_type, mime::TEXT_PLAIN);
+        assert_eq!(data, b"Hello, World");
+
+        let (content_type, data) = parse_data_uri("data:;charset=utf-8,caf%C3%A9").unwrap();
+        assert_eq!(content_type, mime::TEXT_PLAIN_UTF_8);
+        assert_eq!(data, "café".as_bytes());
+
+        let (content_type, data) = parse_data_uri("DATA:text/csv,a,b").unwrap();
+        assert_eq!(content_type, mime::TEXT_CSV);
+        assert_eq!(data, b"a,b");
+
+        assert!(parse_data_uri("http://example.com").is_err());
+        assert!(parse_data_uri("data:text/plain").is_err());
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn test_base64_data_uri() {
+        let (_, data) = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_data_uri(
+                "img",
+                Some("pixel.png"),
+                "data:image/png;base64,iVBORw0KGgoAAAANSUhEUg",
+            )
+            .unwrap()
+            .finish()
+            .unwrap();
+        let mut expected = b"--B\r\n\
+            Content-Disposition: form-data; name=\"img\"; filename=\"pixel.png\"\r\n\
+            Content-Type: image/png\r\n\
+            \r\n"
+            .to_vec();
+        expected.extend_from_slice(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\r\n--B--\r\n");
+        assert_eq!(data, expected);
+
+        let err = parse_data_uri("data:image/png;base64,!!!").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{check_header, MultipartBuilder, Part};
+use base64::Engine;
+use std::io;
+
+/// digest of the data of a part, sent as a part header
+///
+/// Variants depend on the enabled features, matches need a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DigestAlgorithm {
+    /// `Content-MD5: <base64 md5>`, needs the `md5` feature
+    #[cfg(feature = "md5")]
+    Md5,
+    /// `Digest: sha-256=<base64 sha-256>`, needs the `sha2` feature
+    #[cfg(feature = "sha2")]
+    Sha256,
+}
+
+impl DigestAlgorithm {
+    /// the header for a digest
+    fn header(self, digest: &[u8]) -> (String, String) {
+        let digest = base64::engine::general_purpose::STANDARD.encode(digest);
+        match self {
+            #[cfg(feature = "md5")]
+            DigestAlgorithm::Md5 => ("Content-MD5".to_string(), digest),
+            #[cfg(feature = "sha2")]
+            DigestAlgorithm::Sha256 => ("Digest".to_string(), format!("sha-256={digest}")),
+        }
+    }
+    fn compute(self, part: &Part) -> io::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "md5")]
+            DigestAlgorithm::Md5 => {
+                use md5::Digest;
+                let mut hasher = md5::Md5::new();
+                io::copy(&mut part.data_reader()?, &mut hasher)?;
+                Ok(hasher.finalize().to_vec())
+            }
+            #[cfg(feature = "sha2")]
+            DigestAlgorithm::Sha256 => crate::manifest::sha256(part),
+        }
+    }
+}
+
+impl MultipartBuilder {
+    /// add a digest header to the parts called `name` added so far
+    ///
+    /// * name field name
+    /// * algorithm the digest to compute
+    ///
+    /// The digest is computed right away, over the data before any transfer
+    /// encoding. Files are read to be hashed, streams that can only be read
+    /// once fail.
+    pub fn with_part_digest(mut self, name: &str, algorithm: DigestAlgorithm) -> io::Result<Self> {
+        for part in self.parts.iter_mut().filter(|part| part.name == name) {
+            let header = algorithm.header(&algorithm.compute(part)?);
+            part.headers.push(header);
+        }
+        Ok(self)
+    }
+    /// add a precomputed digest header to the parts called `name`
+    ///
+    /// * name field name
+    /// * algorithm the digest algorithm
+    /// * digest the raw digest, base64 encoded in the header
+    pub fn with_part_digest_value(
+        mut self,
+        name: &str,
+        algorithm: DigestAlgorithm,
+        digest: &[u8],
+    ) -> io::Result<Self> {
+        let (header, value) = algorithm.header(digest);
+        check_header(&header, &value)?;
+        for part in self.parts.iter_mut().filter(|part| part.name == name) {
+            part.headers.push((header.clone(), value.clone()));
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn headers(builder: MultipartBuilder, name: &str) -> Vec<String> {
+        let (_, data) = builder.finish().unwrap();
+        String::from_utf8(data)
+            .unwrap()
+            .lines()
+            .filter(|line| line.starts_with(name))
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[cfg(feature = "md5")]
+    #[test]
+    fn test_md5_digest() {
+        let builder = MultipartBuilder::new()
+            .add_text("a", "hello")
+            .unwrap()
+            .add_text("b", "")
+            .unwrap()
+            .with_part_digest("a", DigestAlgorithm::Md5)
+            .unwrap()
+            .with_part_digest_value("b", DigestAlgorithm::Md5, &[0; 16])
+            .unwrap();
+        assert_eq!(
+            headers(builder, "Content-MD5"),
+            [
+                "Content-MD5: XUFAKrxLKna5cZ2REBfFkg==",
+                "Content-MD5: AAAAAAAAAAAAAAAAAAAAAA==",
+            ]
+        );
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn test_sha256_digest() {
+        let builder = MultipartBuilder::new()
+            .add_text("a", "hello")
+            .unwrap()
+            .with_part_digest("a", DigestAlgorithm::Sha256)
+            .unwrap();
+        assert_eq!(
+            headers(builder, "Digest"),
+            ["Digest: sha-256=LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ="]
+        );
+        let builder = MultipartBuilder::new()
+            .add_reader("r", None, None, io::empty())
+            .unwrap();
+        assert!(builder
+            .with_part_digest("r", DigestAlgorithm::Sha256)
+            .is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{mime_filename, Body, MultipartBuilder};
+use mime::Mime;
+use std::collections::HashSet;
+use std::fs::{self, DirEntry, ReadDir};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// content type of the parts holding a symlink target
+const SYMLINK_TYPE: &str = "inode/symlink";
+
+/// what [`add_file`](MultipartBuilder::add_file) and
+/// [`add_dir`](MultipartBuilder::add_dir) do with symlinks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SymlinkMode {
+    /// send what the link points to
+    #[default]
+    Follow,
+    /// send the link target path, with the `inode/symlink` content type
+    StoreTarget,
+    /// leave symlinks out
+    Skip,
+}
+
+impl MultipartBuilder {
+    /// what to do with the symlinks added as files, following them by default
+    pub fn with_symlink_mode(mut self, mode: SymlinkMode) -> Self {
+        self.symlink_mode = mode;
+        self
+    }
+    /// add every file under a directory, under the same field name
+    ///
+    /// * name file field name
+    /// * path the directory, walked right away
+    ///
+    /// Files are added like with [`add_file`](Self::add_file), sorted by
+    /// path, and their filename is their path relative to `path`, with `/`
+    /// separators. Symlinks follow the [mode](Self::with_symlink_mode), a
+    /// followed link to a directory already walked is left out.
+    pub fn add_dir<P: AsRef<Path>>(self, name: &str, path: P) -> io::Result<Self> {
+        let root = path.as_ref();
+        let mut visited = HashSet::new();
+        self.walk_dir(name, root, root, &mut visited)
+    }
+    /// add the files of a directory listing that pass a filter
+    ///
+    /// * name file field name
+    /// * dir the listing, e.g. from [`fs::read_dir`]
+    /// * filter whether an entry is added, e.g. by extension or size
+    ///
+    /// Kept entries are sorted by path and added like with
+    /// [`add_file`](Self::add_file), directories are skipped. Unlike
+    /// [`add_dir`](Self::add_dir) subdirectories aren't walked.
+    pub fn add_read_dir<F>(mut self, name: &str, dir: ReadDir, filter: F) -> io::Result<Self>
+    where
+        F: Fn(&DirEntry) -> bool,
+    {
+        let mut paths = Vec::new();
+        for entry in dir {
+            let entry = entry?;
+            if filter(&entry) && !entry.path().is_dir() {
+                paths.push(entry.path());
+            }
+        }
+        paths.sort();
+        for path in paths {
+            self = self.add_file(name, path)?;
+        }
+        Ok(self)
+    }
+    fn walk_dir(
+        mut self,
+        name: &str,
+        root: &Path,
+        dir: &Path,
+        visited: &mut HashSet<PathBuf>,
+    ) -> io::Result<Self> {
+        if !visited.insert(fs::canonicalize(dir)?) {
+            return Ok(self);
+        }
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.sort();
+        for path in paths {
+            let link = fs::symlink_metadata(&path)?.is_symlink();
+            if path.is_dir() && (!link || self.symlink_mode == SymlinkMode::Follow) {
+                self = self.walk_dir(name, root, &path, visited)?;
+                continue;
+            }
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            let filename = relative
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let (content_type, _) = mime_filename(&path);
+            self = self.add_path(name, &path, Some(&filename), content_type)?;
+        }
+        Ok(self)
+    }
+    /// add a file, or what the [mode](Self::with_symlink_mode) says for a
+    /// symlink
+    pub(crate) fn add_path(
+        self,
+        name: &str,
+        path: &Path,
+        filename: Option<&str>,
+        content_type: Mime,
+    ) -> io::Result<Self> {
+        if self.check_symlink(path)? {
+            match self.symlink_mode {
+                SymlinkMode::Follow => {}
+                SymlinkMode::StoreTarget => {
+                    let target = fs::read_link(path)?;
+                    let body = Body::Bytes(target.to_string_lossy().into_owned().into_bytes());
+                    let content_type = SYMLINK_TYPE.parse().expect("valid mime");
+                    return self.push_part(name, filename, Some(content_type), &[], body);
+                }
+                SymlinkMode::Skip => return Ok(self),
+            }
+        }
+        self.check_open(path)?;
+        let body = Body::File(path.to_path_buf());
+        self.push_part(name, filename, Some(content_type), &[], body)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    fn tree(mode: SymlinkMode) -> Vec<(String, String, String)> {
+        let dir = std::env::temp_dir().join(format!(
+            "ureq-multipart-dir-{mode:?}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("sub/b.txt"), "b").unwrap();
+        symlink("a.txt", dir.join("link.txt")).unwrap();
+        // a cycle, only walked once when followed
+        symlink("..", dir.join("sub/up")).unwrap();
+        let (_, data) = MultipartBuilder::new()
+            .with_symlink_mode(mode)
+            .add_dir("files", &dir)
+            .unwrap()
+            .finish()
+            .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let data = String::from_utf8(data).unwrap();
+        data.split("\r\n\r\n")
+            .zip(data.split("\r\n\r\n").skip(1))
+            .filter_map(|(head, body)| {
+                let filename = head.split("filename=\"").nth(1)?.split('"').next()?;
+                let content_type = head.split("Content-Type: ").nth(1)?.split("\r\n").next()?;
+                let body = body.split("\r\n").next()?;
+                Some((filename.into(), content_type.into(), body.into()))
+            })
+            .collect()
+    }
+
+    fn entry(filename: &str, content_type: &str, body: &str) -> (String, String, String) {
+        (filename.into(), content_type.into(), body.into())
+    }
+
+    #[test]
+    fn test_symlink_modes() {
+        let text = "text/plain";
+        assert_eq!(
+            tree(SymlinkMode::Follow),
+            [
+                entry("a.txt", text, "a"),
+                entry("link.txt", text, "a"),
+                entry("sub/b.txt", text, "b"),
+            ]
+        );
+        assert_eq!(
+            tree(SymlinkMode::StoreTarget),
+            [
+                entry("a.txt", text, "a"),
+                entry("link.txt", SYMLINK_TYPE, "a.txt"),
+                entry("sub/b.txt", text, "b"),
+                entry("sub/up", SYMLINK_TYPE, ".."),
+            ]
+        );
+        assert_eq!(
+            tree(SymlinkMode::Skip),
+            [entry("a.txt", text, "a"), entry("sub/b.txt", text, "b")]
+        );
+    }
+
+    #[test]
+    fn test_add_file_symlink() {
+        let dir = std::env::temp_dir().join(format!("ureq-multipart-link-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        let link = dir.join("link.txt");
+        let _ = fs::remove_file(&link);
+        symlink("a.txt", &link).unwrap();
+        let builder = MultipartBuilder::new()
+            .with_symlink_mode(SymlinkMode::Skip)
+            .add_file("file", &link)
+            .unwrap();
+        assert!(builder.parts.is_empty());
+        let builder = MultipartBuilder::new()
+            .with_symlink_mode(SymlinkMode::StoreTarget)
+            .add_file("file", &link)
+            .unwrap();
+        assert_eq!(builder.parts[0].filename.as_deref(), Some("link.txt"));
+        assert!(matches!(&builder.parts[0].body, Body::Bytes(data) if data == b"a.txt"));
+        let builder = MultipartBuilder::new().add_file("file", &link).unwrap();
+        assert!(matches!(&builder.parts[0].body, Body::File(path) if *path == link));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_add_read_dir() {
+        let dir =
+            std::env::temp_dir().join(format!("ureq-multipart-read-dir-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub.txt")).unwrap();
+        for file in ["b.txt", "a.txt", "c.log", "d.TXT"] {
+            fs::write(dir.join(file), file).unwrap();
+        }
+        let builder = MultipartBuilder::new()
+            .add_read_dir("files", fs::read_dir(&dir).unwrap(), |entry| {
+                entry.path().extension().is_some_and(|ext| ext == "txt")
+            })
+            .unwrap();
+        let filenames: Vec<_> = builder
+            .parts
+            .iter()
+            .map(|part| part.filename.as_deref().unwrap())
+            .collect();
+        assert_eq!(filenames, ["a.txt", "b.txt"]);
+        assert!(builder.parts.iter().all(|part| part.name == "files"));
+
+        let builder = MultipartBuilder::new()
+            .add_read_dir("files", fs::read_dir(&dir).unwrap(), |_| false)
+            .unwrap();
+        assert!(builder.parts.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{MultipartBuilder, Part};
+use std::fmt;
+use std::io;
+
+/// the `Content-Transfer-Encoding` of a part
+///
+/// HTTP doesn't need one, some gateways, e.g. to email, do. Only
+/// [`Base64`](Self::Base64) changes the data, the other ones just label it and
+/// it's up to the caller to add data that matches. Variants depend on the
+/// enabled features, matches need a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum TransferEncoding {
+    /// any bytes, with no line length limit
+    Binary,
+    /// `8bit`, lines of non NUL bytes
+    EightBit,
+    /// `7bit`, lines of US-ASCII
+    SevenBit,
+    /// the data is base64 encoded as it's written, in CRLF separated lines of
+    /// 76 chars per RFC 2045, needs the `base64` feature
+    #[cfg(feature = "base64")]
+    Base64,
+}
+
+impl TransferEncoding {
+    /// length of `len` bytes of data once encoded
+    pub(crate) fn encoded_len(self, len: u64) -> u64 {
+        match self {
+            #[cfg(feature = "base64")]
+            TransferEncoding::Base64 => {
+                let lines = len.div_ceil(crate::stream::BASE64_LINE_BYTES as u64);
+                len.div_ceil(3) * 4 + lines.saturating_sub(1) * 2
+            }
+            _ => len,
+        }
+    }
+}
+
+impl fmt::Display for TransferEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TransferEncoding::Binary => "binary",
+            TransferEncoding::EightBit => "8bit",
+            TransferEncoding::SevenBit => "7bit",
+            #[cfg(feature = "base64")]
+            TransferEncoding::Base64 => "base64",
+        })
+    }
+}
+
+impl MultipartBuilder {
+    /// transfer encoding of every part that doesn't set its own
+    ///
+    /// Parts get a `Content-Transfer-Encoding` header, and their data is
+    /// encoded accordingly. Nested multipart bodies are parts too, their own
+    /// parts keep the encoding of the nested builder.
+    pub fn with_default_transfer_encoding(mut self, encoding: TransferEncoding) -> Self {
+        self.default_transfer_encoding = Some(encoding);
+        self
+    }
+    /// transfer encoding of the parts called `name` added so far
+    ///
+    /// Takes precedence over the
+    /// [default](Self::with_default_transfer_encoding) one.
+    pub fn with_part_transfer_encoding(mut self, name: &str, encoding: TransferEncoding) -> Self {
+        for part in self.parts.iter_mut().filter(|part| part.name == name) {
+            part.transfer_encoding = Some(encoding);
+        }
+        self
+    }
+    pub(crate) fn part_encoding(&self, part: &Part) -> Option<TransferEncoding> {
+        part.transfer_encoding.or(self.default_transfer_encoding)
+    }
+    /// length of the encoded data of a part, when known without reading it
+    pub(crate) fn part_len(&self, part: &Part) -> io::Result<Option<u64>> {
+        let len = part.body.known_len()?;
+        Ok(match self.part_encoding(part) {
+            Some(encoding) => len.map(|len| encoding.encoded_len(len)),
+            None => len,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_transfer_encoding() {
+        let (_, data) = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .with_default_transfer_encoding(TransferEncoding::EightBit)
+            .add_text("a", "1")
+            .unwrap()
+            .add_text("b", "2")
+            .unwrap()
+            .with_part_transfer_encoding("b", TransferEncoding::SevenBit)
+            .finish()
+            .unwrap();
+        let expected = "--B\r\nContent-Disposition: form-data; name=\"a\"\r\n\
+            Content-Transfer-Encoding: 8bit\r\n\r\n1\r\n\
+            --B\r\nContent-Disposition: form-data; name=\"b\"\r\n\
+            Content-Transfer-Encoding: 7bit\r\n\r\n2\r\n--B--\r\n";
+        assert_eq!(String::from_utf8(data).unwrap(), expected);
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn test_base64_transfer_encoding() {
+        let builder = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .with_default_transfer_encoding(TransferEncoding::Base64)
+            .add_text("a", "hello")
+            .unwrap()
+            .add_reader("b", None, None, io::Cursor::new(b"hi".to_vec()))
+            .unwrap();
+        assert_eq!(builder.part_len(&builder.parts[0]).unwrap(), Some(8));
+        let (_, data) = builder.finish().unwrap();
+        let expected = "--B\r\nContent-Disposition: form-data; name=\"a\"\r\n\
+            Content-Transfer-Encoding: base64\r\n\r\naGVsbG8=\r\n\
+            --B\r\nContent-Disposition: form-data; name=\"b\"\r\n\
+            Content-Type: application/octet-stream\r\n\
+            Content-Transfer-Encoding: base64\r\n\r\naGk=\r\n--B--\r\n";
+        assert_eq!(String::from_utf8(data).unwrap(), expected);
+    }
+
+    /// hands out a few bytes per read
+    #[cfg(feature = "base64")]
+    struct Trickle(io::Cursor<Vec<u8>>);
+
+    #[cfg(feature = "base64")]
+    impl io::Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(10);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn test_base64_line_length() {
+        use base64::Engine;
+        let engine = base64::engine::general_purpose::STANDARD;
+        for len in [0, 1, 56, 57, 58, 114, 1000, 5000] {
+            let raw: Vec<u8> = (0..len).map(|idx| (idx % 251) as u8).collect();
+            let builder = MultipartBuilder::new()
+                .with_boundary("B")
+                .unwrap()
+                .with_default_transfer_encoding(TransferEncoding::Base64)
+                .add_bytes("a", None, None, &raw)
+                .unwrap()
+                .add_reader("b", None, None, Trickle(io::Cursor::new(raw.clone())))
+                .unwrap();
+            let expected_len = builder.part_len(&builder.parts[0]).unwrap().unwrap();
+            let (_, data) = builder.finish().unwrap();
+            let data = String::from_utf8(data).unwrap();
+            for body in data.split("\r\n\r\n").skip(1) {
+                let body = body.split("\r\n--B").next().unwrap();
+                assert_eq!(body.len() as u64, expected_len);
+                let lines: Vec<_> = body.split("\r\n").collect();
+                assert!(lines.iter().all(|line| line.len() <= 76));
+                assert!(lines[..lines.len() - 1].iter().all(|line| line.len() == 76));
+                assert_eq!(engine.decode(lines.concat()).unwrap(), raw);
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{MultipartBuilder, MultipartError};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+impl MultipartBuilder {
+    /// add a text field for every `KEY=VALUE` line of an env file
+    ///
+    /// * path the file to read
+    ///
+    /// Parsing rules:
+    /// * blank lines and lines starting with `#` are skipped
+    /// * an `export ` prefix is ignored, keys and unquoted values are trimmed
+    /// * unquoted values end at a ` #` comment
+    /// * single quoted values are taken literally
+    /// * double quoted values support the `\n`, `\"` and `\\` escapes
+    ///
+    /// Lines without `=`, with an empty key or an unterminated quote fail.
+    pub fn add_env_file<P: AsRef<Path>>(mut self, path: P) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        for (idx, line) in content.lines().enumerate() {
+            if let Some((key, value)) = parse_env_line(line, idx + 1)? {
+                self = self.add_text(&key, &value)?;
+            }
+        }
+        Ok(self)
+    }
+}
+
+fn invalid(line: usize, reason: &str) -> io::Error {
+    MultipartError::InvalidEnvLine {
+        line,
+        reason: reason.to_string(),
+    }
+    .into()
+}
+
+fn parse_env_line(line: &str, number: usize) -> io::Result<Option<(String, String)>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let (key, value) = line
+        .split_once('=')
+        .ok_or_else(|| invalid(number, "missing `=`"))?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(invalid(number, "empty key"));
+    }
+    let value = value.trim_start();
+    let value = if let Some(quoted) = value.strip_prefix('\'') {
+        let (value, _) = quoted
+            .split_once('\'')
+            .ok_or_else(|| invalid(number, "unterminated quote"))?;
+        value.to_string()
+    } else if let Some(quoted) = value.strip_prefix('"') {
+        unescape(quoted).ok_or_else(|| invalid(number, "unterminated quote"))?
+    } else {
+        let value = value.split_once(" #").map_or(value, |(value, _)| value);
+        value.trim_end().to_string()
+    };
+    Ok(Some((key.to_string(), value)))
+}
+
+/// the content of a double quoted string, up to its closing quote
+fn unescape(quoted: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(out),
+            '\\' => match chars.next()? {
+                'n' => out.push('\n'),
+                escaped @ ('"' | '\\') => out.push(escaped),
+                other => {
+                    out.push('\\');
+                    out.push(other);
+                }
+            },
+            _ => out.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_env_file() {
+        let path = std::env::temp_dir().join(format!("ureq-multipart-{}.env", std::process::id()));
+        let env = "# settings\n\
+            \n\
+            NAME=value # trailing comment\n\
+            export TOKEN = abc\n\
+            SINGLE='a # b'\n\
+            DOUBLE=\"line\\nnext \\\"quoted\\\"\"\n\
+            EMPTY=\n";
+        fs::write(&path, env).unwrap();
+        let builder = MultipartBuilder::new().add_env_file(&path).unwrap();
+        assert_eq!(
+            builder.to_urlencoded_pairs().unwrap(),
+            [
+                ("NAME", "value"),
+                ("TOKEN", "abc"),
+                ("SINGLE", "a # b"),
+                ("DOUBLE", "line\nnext \"quoted\""),
+                ("EMPTY", ""),
+            ]
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+        );
+
+        fs::write(&path, "OK=1\nbroken\n").unwrap();
+        let err = MultipartBuilder::new().add_env_file(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        let err = err.into_inner().unwrap().downcast::<MultipartError>();
+        assert!(matches!(
+            *err.unwrap(),
+            MultipartError::InvalidEnvLine { line: 2, .. }
+        ));
+    }
+}
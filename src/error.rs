@@ -0,0 +1,156 @@
+use std::fmt;
+use std::io;
+
+/// errors specific to building multipart bodies
+///
+/// The builder methods return `io::Result`, these errors are wrapped into the
+/// `io::Error`, use [`io::Error::get_ref`] and downcast to match on them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MultipartError {
+    /// the boundary is empty, too long or uses chars RFC 2046 doesn't allow
+    InvalidBoundary { boundary: String, reason: String },
+    /// a part header would break the part framing
+    InvalidHeader { name: String, value: String },
+    /// an empty byte range was requested
+    EmptyRange { start: u64, end: u64 },
+    /// a remote resource answered with an unexpected status
+    UnexpectedStatus { url: String, status: u16 },
+    /// a part is a stream that can only be read once
+    OneShotPart { name: String },
+    /// a `data:` URI couldn't be parsed or decoded
+    InvalidDataUri { reason: String },
+    /// the data contains the boundary, and no other boundary could be used
+    BoundaryCollision { boundary: String, attempts: usize },
+    /// the body length is needed, but a part is a stream of unknown length
+    UnknownLength { name: String },
+    /// the body is already larger than the padding target
+    PadTargetExceeded { len: u64, target: usize },
+    /// nested multipart bodies go deeper than allowed
+    NestingTooDeep { depth: usize, max: usize },
+    /// a line of an env file is not a `KEY=VALUE` pair
+    InvalidEnvLine { line: usize, reason: String },
+    /// a text field is not valid UTF-8
+    InvalidUtf8 { name: String, valid_up_to: usize },
+    /// a command whose output is a part exited unsuccessfully
+    CommandFailed { program: String, code: Option<i32> },
+    /// a field required by the upload scheme is missing
+    MissingField { name: String },
+    /// a field uses a name the upload scheme reserves
+    ReservedField { name: String },
+    /// base64 data couldn't be decoded
+    InvalidBase64 { reason: String },
+    /// a glob pattern is invalid, or matches no file
+    InvalidGlob { pattern: String, reason: String },
+    /// a part has a content type but no filename, so it may pass for a text field
+    MissingFilename { name: String },
+    /// a placeholder part was never filled
+    UnfilledPlaceholder { name: String },
+    /// there is no unfilled placeholder part with that name
+    NoPlaceholder { name: String },
+    /// a part header is set more than once
+    DuplicateHeader { name: String },
+    /// a body doesn't parse as multipart, or doesn't round-trip
+    MalformedBody { reason: String },
+    /// a part's input is larger than allowed
+    PartTooLarge { name: String, max: u64 },
+    /// a range goes past the end of its data
+    RangeOutOfBounds { start: u64, end: u64, len: u64 },
+    /// a JSON Patch document isn't an array of operations
+    InvalidJsonPatch { reason: String },
+    /// a part can't be saved to be restored later, e.g. a stream
+    NotResumable { name: String },
+}
+
+impl fmt::Display for MultipartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidBoundary { boundary, reason } => {
+                write!(f, "invalid boundary {boundary:?}: {reason}")
+            }
+            Self::InvalidHeader { name, value } => {
+                write!(f, "invalid part header {name:?}: {value:?}")
+            }
+            Self::EmptyRange { start, end } => write!(f, "empty byte range {start}..{end}"),
+            Self::UnexpectedStatus { url, status } => {
+                write!(f, "unexpected status {status} fetching {url}")
+            }
+            Self::OneShotPart { name } => {
+                write!(f, "part {name:?} is a stream that can only be read once")
+            }
+            Self::InvalidDataUri { reason } => write!(f, "invalid data URI: {reason}"),
+            Self::BoundaryCollision { boundary, attempts } => write!(
+                f,
+                "data contains the boundary {boundary:?}, gave up after {attempts} new boundaries"
+            ),
+            Self::UnknownLength { name } => write!(f, "part {name:?} has an unknown length"),
+            Self::PadTargetExceeded { len, target } => {
+                write!(
+                    f,
+                    "body of {len} bytes exceeds the padding target of {target}"
+                )
+            }
+            Self::NestingTooDeep { depth, max } => {
+                write!(f, "nesting depth {depth} exceeds the maximum of {max}")
+            }
+            Self::InvalidEnvLine { line, reason } => {
+                write!(f, "invalid env file line {line}: {reason}")
+            }
+            Self::InvalidUtf8 { name, valid_up_to } => write!(
+                f,
+                "text field {name:?} is not valid UTF-8 after byte {valid_up_to}"
+            ),
+            Self::CommandFailed {
+                program,
+                code: Some(code),
+            } => write!(f, "command {program:?} exited with code {code}"),
+            Self::CommandFailed {
+                program,
+                code: None,
+            } => write!(f, "command {program:?} was killed by a signal"),
+            Self::MissingField { name } => write!(f, "missing required field {name:?}"),
+            Self::ReservedField { name } => write!(f, "field name {name:?} is reserved"),
+            Self::InvalidBase64 { reason } => write!(f, "invalid base64: {reason}"),
+            Self::InvalidGlob { pattern, reason } => {
+                write!(f, "glob pattern {pattern:?}: {reason}")
+            }
+            Self::MissingFilename { name } => {
+                write!(f, "part {name:?} has a content type but no filename")
+            }
+            Self::UnfilledPlaceholder { name } => {
+                write!(f, "placeholder part {name:?} is not filled")
+            }
+            Self::NoPlaceholder { name } => write!(f, "no unfilled placeholder part {name:?}"),
+            Self::DuplicateHeader { name } => {
+                write!(f, "part header {name:?} is set more than once")
+            }
+            Self::MalformedBody { reason } => write!(f, "malformed multipart body: {reason}"),
+            Self::PartTooLarge { name, max } => {
+                write!(f, "part {name:?} is larger than {max} bytes")
+            }
+            Self::RangeOutOfBounds { start, end, len } => {
+                write!(f, "range {start}..{end} goes past the end, at {len}")
+            }
+            Self::InvalidJsonPatch { reason } => {
+                write!(f, "invalid JSON Patch: {reason}")
+            }
+            Self::NotResumable { name } => {
+                write!(f, "part {name:?} can't be saved")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MultipartError {}
+
+impl From<MultipartError> for io::Error {
+    fn from(err: MultipartError) -> Self {
+        let kind = match err {
+            MultipartError::UnexpectedStatus { .. } | MultipartError::CommandFailed { .. } => {
+                io::ErrorKind::Other
+            }
+            _ => io::ErrorKind::InvalidInput,
+        };
+        io::Error::new(kind, err)
+    }
+}
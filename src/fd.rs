@@ -0,0 +1,61 @@
+use crate::MultipartBuilder;
+use mime::Mime;
+use std::fs::File;
+use std::io;
+use std::os::fd::{FromRawFd, RawFd};
+
+impl MultipartBuilder {
+    /// add the data read from an open file descriptor, e.g. one inherited
+    /// from the parent process
+    ///
+    /// * name field name
+    /// * filename optional file name
+    /// * content_type defaults to `application/octet-stream`
+    /// * fd the descriptor, read when the body is produced
+    ///
+    /// The builder takes ownership of the descriptor: it's closed once the
+    /// body was produced, or when the builder is dropped. Like
+    /// [`add_reader`](Self::add_reader), the part can only be read once.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open descriptor owned by the caller, which must not
+    /// use nor close it afterwards.
+    pub unsafe fn add_fd(
+        self,
+        name: &str,
+        filename: Option<&str>,
+        content_type: Option<Mime>,
+        fd: RawFd,
+    ) -> io::Result<Self> {
+        let file = File::from_raw_fd(fd);
+        self.add_reader(name, filename, content_type, file)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+    use std::os::fd::IntoRawFd;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn test_add_fd() {
+        let (reader, mut writer) = UnixStream::pair().unwrap();
+        writer.write_all(b"piped").unwrap();
+        drop(writer);
+        let fd = reader.into_raw_fd();
+        let builder = unsafe {
+            MultipartBuilder::new()
+                .with_boundary("B")
+                .unwrap()
+                .add_fd("fd", Some("p.txt"), Some(mime::TEXT_PLAIN), fd)
+                .unwrap()
+        };
+        let (_, data) = builder.finish().unwrap();
+        let expected = "--B\r\nContent-Disposition: form-data; name=\"fd\"; filename=\"p.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\npiped\r\n--B--\r\n";
+        assert_eq!(String::from_utf8(data).unwrap(), expected);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{MultipartBuilder, MultipartError};
+use std::io;
+
+impl MultipartBuilder {
+    /// add every file matching a glob pattern, under the same field name
+    ///
+    /// * name file field name
+    /// * pattern e.g. `logs/*.txt`, see the `glob` crate for the syntax
+    ///
+    /// Matches are sorted by path and added like with
+    /// [`add_file`](Self::add_file), so they are only read when the body is
+    /// produced. Directories are skipped. A pattern matching no file fails.
+    pub fn add_glob(mut self, name: &str, pattern: &str) -> io::Result<Self> {
+        let invalid = |reason: String| MultipartError::InvalidGlob {
+            pattern: pattern.to_string(),
+            reason,
+        };
+        let mut paths = Vec::new();
+        for path in glob::glob(pattern).map_err(|err| invalid(err.to_string()))? {
+            let path = path.map_err(io::Error::from)?;
+            if path.is_file() {
+                paths.push(path);
+            }
+        }
+        if paths.is_empty() {
+            return Err(invalid("no matching file".to_string()).into());
+        }
+        paths.sort();
+        for path in paths {
+            self = self.add_file(name, path)?;
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_add_glob() {
+        let dir = std::env::temp_dir().join(format!("ureq-multipart-glob-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub.txt")).unwrap();
+        for file in ["b.txt", "a.txt", "c.log"] {
+            fs::write(dir.join(file), file).unwrap();
+        }
+        let pattern = format!("{}/*.txt", dir.display());
+        let builder = MultipartBuilder::new().add_glob("files", &pattern).unwrap();
+        let filenames: Vec<_> = builder
+            .parts
+            .iter()
+            .map(|part| (part.name.as_str(), part.filename.as_deref().unwrap()))
+            .collect();
+        assert_eq!(filenames, [("files", "a.txt"), ("files", "b.txt")]);
+
+        let err = MultipartBuilder::new()
+            .add_glob("files", &format!("{}/*.csv", dir.display()))
+            .unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+        let err = err.into_inner().unwrap().downcast::<MultipartError>();
+        assert!(matches!(*err.unwrap(), MultipartError::InvalidGlob { .. }));
+        assert!(MultipartBuilder::new().add_glob("files", "[").is_err());
+    }
+}
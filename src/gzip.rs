@@ -0,0 +1,86 @@
+use crate::{mime_filename, Body, MultipartBuilder};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+impl MultipartBuilder {
+    /// add a file, gzip compressed when that makes it smaller
+    ///
+    /// * name file field name
+    /// * path the file, read and compressed right away
+    ///
+    /// The filename and content type are the file's. A compressed part gets
+    /// a `Content-Encoding: gzip` header, the server has to decode it: form
+    /// parsers usually ignore the encoding of single parts.
+    pub fn add_file_maybe_gzip<P: AsRef<Path>>(self, name: &str, path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let (content_type, filename) = mime_filename(path);
+        let data = fs::read(path)?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&data)?;
+        let compressed = encoder.finish()?;
+        let (data, headers): (_, &[_]) = if compressed.len() < data.len() {
+            (compressed, &[("Content-Encoding", "gzip")])
+        } else {
+            (data, &[])
+        };
+        self.push_part(
+            name,
+            filename,
+            Some(content_type),
+            headers,
+            Body::Bytes(data),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use rand::RngCore;
+    use std::io::Read;
+
+    fn gzip_part(data: &[u8]) -> (Vec<(String, String)>, Vec<u8>) {
+        let path = std::env::temp_dir().join(format!(
+            "ureq-multipart-gzip-{}-{}.bin",
+            std::process::id(),
+            data.len()
+        ));
+        fs::write(&path, data).unwrap();
+        let mut builder = MultipartBuilder::new()
+            .add_file_maybe_gzip("file", &path)
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+        let part = builder.parts.pop().unwrap();
+        assert_eq!(part.filename.as_deref(), path.file_name().unwrap().to_str());
+        let Body::Bytes(data) = part.body else {
+            panic!("in memory part expected");
+        };
+        (part.headers, data)
+    }
+
+    #[test]
+    fn test_add_file_maybe_gzip() {
+        let text = "all work and no play\n".repeat(500);
+        let (headers, data) = gzip_part(text.as_bytes());
+        assert_eq!(
+            headers,
+            [("Content-Encoding".to_string(), "gzip".to_string())]
+        );
+        assert!(data.len() < text.len());
+        let mut decoded = String::new();
+        GzDecoder::new(&data[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, text);
+
+        let mut noise = vec![0; 4096];
+        rand::thread_rng().fill_bytes(&mut noise);
+        let (headers, data) = gzip_part(&noise);
+        assert!(headers.is_empty());
+        assert_eq!(data, noise);
+    }
+}
@@ -0,0 +1,84 @@
+use crate::{MultipartBuilder, MultipartError};
+use serde_json::Value;
+use std::io;
+
+/// operations defined by RFC 6902
+const OPS: [&str; 6] = ["add", "remove", "replace", "move", "copy", "test"];
+
+impl MultipartBuilder {
+    /// add a JSON Patch document, per RFC 6902
+    ///
+    /// * name field name
+    /// * patch an array of operations, serialized right away
+    ///
+    /// Each operation must be an object with a known `op` and a string
+    /// `path`, the other members aren't checked. The part has no filename and
+    /// the `application/json-patch+json` content type.
+    pub fn add_json_patch(self, name: &str, patch: &Value) -> io::Result<Self> {
+        let invalid = |reason: String| MultipartError::InvalidJsonPatch { reason };
+        let ops = patch
+            .as_array()
+            .ok_or_else(|| invalid("not an array".to_string()))?;
+        for (idx, op) in ops.iter().enumerate() {
+            let kind = op.get("op").and_then(Value::as_str);
+            if !kind.is_some_and(|kind| OPS.contains(&kind)) {
+                return Err(invalid(format!("operation {idx} has no valid op")).into());
+            }
+            if !op.get("path").is_some_and(Value::is_string) {
+                return Err(invalid(format!("operation {idx} has no path")).into());
+            }
+        }
+        let data = serde_json::to_vec(patch)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let content_type = "application/json-patch+json".parse().expect("valid mime");
+        self.add_bytes(name, None, Some(content_type), &data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Body;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_patch() {
+        let patch = json!([
+            { "op": "replace", "path": "/title", "value": "new" },
+            { "op": "remove", "path": "/draft" },
+        ]);
+        let builder = MultipartBuilder::new()
+            .add_json_patch("patch", &patch)
+            .unwrap();
+        let part = &builder.parts[0];
+        assert_eq!(
+            part.content_type.as_ref().unwrap().essence_str(),
+            "application/json-patch+json"
+        );
+        let Body::Bytes(data) = &part.body else {
+            panic!("in memory part expected");
+        };
+        let decoded: Value = serde_json::from_slice(data).unwrap();
+        assert_eq!(decoded, patch);
+
+        assert!(MultipartBuilder::new()
+            .add_json_patch("patch", &json!([]))
+            .is_ok());
+        for patch in [
+            json!({ "op": "add", "path": "/a" }),
+            json!([{ "op": "merge", "path": "/a" }]),
+            json!([{ "op": "add", "path": 1 }]),
+            json!(["add"]),
+        ] {
+            let err = MultipartBuilder::new()
+                .add_json_patch("patch", &patch)
+                .unwrap_err();
+            let err = err
+                .into_inner()
+                .unwrap()
+                .downcast::<MultipartError>()
+                .unwrap();
+            assert!(matches!(*err, MultipartError::InvalidJsonPatch { .. }));
+        }
+    }
+}
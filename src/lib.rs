@@ -34,39 +34,520 @@
 //! ```
 use mime::Mime;
 use rand::Rng;
-use std::fs::File;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
 use std::io;
 use std::io::prelude::*;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Duration;
 use ureq::{Error, Request, Response};
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "base64")]
+mod b64;
+mod boundary;
+mod command;
+#[cfg(feature = "crc32fast")]
+mod crc;
+mod data_uri;
+#[cfg(any(feature = "md5", feature = "sha2"))]
+mod digest;
+mod dir;
+mod encoding;
+mod env_file;
+mod error;
+#[cfg(unix)]
+mod fd;
+#[cfg(feature = "glob")]
+mod glob_files;
+#[cfg(feature = "flate2")]
+mod gzip;
+#[cfg(feature = "serde")]
+mod json_patch;
+#[cfg(feature = "sha2")]
+mod manifest;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "rmp-serde")]
+mod msgpack;
+#[cfg(feature = "serde")]
+mod ndjson;
+mod nested;
+mod open_retry;
+#[cfg(feature = "parser")]
+mod parser;
+mod rewrite;
+mod rfc5987;
+mod s3;
+#[cfg(feature = "infer")]
+mod sniff;
+#[cfg(feature = "serde")]
+mod state;
+mod stream;
+
+#[cfg(feature = "base64")]
+pub use b64::Base64Alphabet;
+use boundary::SourceBox;
+pub use boundary::{BoundarySource, RandomBoundary};
+#[cfg(any(feature = "md5", feature = "sha2"))]
+pub use digest::DigestAlgorithm;
+pub use dir::SymlinkMode;
+pub use encoding::TransferEncoding;
+pub use error::MultipartError;
+#[cfg(feature = "sha2")]
+pub use manifest::ManifestFormat;
+use open_retry::{FileCheckBox, RealFs};
+#[cfg(feature = "parser")]
+pub use parser::{parse_multipart, ParsedPart};
+pub use rewrite::PartHeaders;
+use rewrite::RewriterBox;
+pub use rfc5987::FilenameEncoding;
+#[cfg(feature = "serde")]
+pub use state::BuilderState;
+pub use stream::{Chunks, FramingStats, MultipartReader, RetryableBody};
+use stream::{ExactReader, GeneratorReader};
 
 const BOUNDARY_LEN: usize = 29;
+const BOUNDARY_PREFIX: &str = "---------------------------";
+const BOUNDARY_ALPHABET: &str = "0123456789";
+const ALPHANUMERIC: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const MAX_BOUNDARY_ATTEMPTS: usize = 8;
+const MAX_NESTING_DEPTH: usize = 4;
+/// longest boundary allowed by RFC 2046
+pub const MAX_BOUNDARY_LEN: usize = 70;
 
 fn opt_filename(path: &Path) -> Option<&str> {
     path.file_name().and_then(|filename| filename.to_str())
 }
 
-fn random_alphanumeric(len: usize) -> String {
-    rand::thread_rng()
-        .sample_iter(&rand::distributions::Uniform::from(0..=9))
-        .take(len)
-        .map(|num| num.to_string())
+/// cut a filename to at most `max` bytes, keeping its extension when it fits
+fn truncate_filename(filename: &str, max: usize) -> String {
+    if filename.len() <= max {
+        return filename.to_string();
+    }
+    let ext = match filename.rfind('.') {
+        Some(idx) if idx > 0 && filename.len() - idx < max => &filename[idx..],
+        _ => "",
+    };
+    let mut end = max - ext.len();
+    while !filename.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{ext}", &filename[..end])
+}
+
+fn random_boundary(alphabet: &[u8], len: usize) -> String {
+    let mut rng = rand::thread_rng();
+    (0..len)
+        .map(|_| alphabet[rng.gen_range(0..alphabet.len())] as char)
         .collect()
 }
 
+/// whether `needle` appears in `haystack`
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
 fn mime_filename(path: &Path) -> (Mime, Option<&str>) {
     let content_type = mime_guess::from_path(path);
     let filename = opt_filename(path);
     (content_type.first_or_octet_stream(), filename)
 }
 
+/// reject header names and values that would break the part framing
+fn check_header(name: &str, value: &str) -> io::Result<()> {
+    let valid_name = !name.is_empty() && name.bytes().all(|b| b.is_ascii_graphic() && b != b':');
+    if !valid_name || value.contains(['\r', '\n']) {
+        return Err(MultipartError::InvalidHeader {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// fold a header line before `width` chars, at spaces, RFC 5322 style
+///
+/// A CRLF is inserted before the space starting each continuation line.
+/// Words longer than `width` are left unbroken.
+fn fold_header(line: &str, width: usize) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut line_len = 0;
+    for (idx, word) in line.split(' ').enumerate() {
+        if idx > 0 {
+            if line_len + 1 + word.len() > width {
+                folded.push_str("\r\n");
+                line_len = 0;
+            }
+            folded.push(' ');
+            line_len += 1;
+        }
+        folded.push_str(word);
+        line_len += word.len();
+    }
+    folded
+}
+
+/// quote a shell word, in single quotes when it isn't plain
+fn shell_quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_./:=@,+%".contains(&b));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// check a boundary against the RFC 2046 grammar, 1 to 70 chars out of a
+/// restricted set, not ending with a space
+fn check_boundary(boundary: &str) -> io::Result<()> {
+    let invalid = |reason: &str| -> io::Result<()> {
+        Err(MultipartError::InvalidBoundary {
+            boundary: boundary.to_string(),
+            reason: reason.to_string(),
+        }
+        .into())
+    };
+    if boundary.is_empty() {
+        return invalid("empty");
+    }
+    if boundary.len() > MAX_BOUNDARY_LEN {
+        return invalid("longer than 70 chars");
+    }
+    let valid_char = |b: u8| b.is_ascii_alphanumeric() || b"'()+_,-./:=? ".contains(&b);
+    if !boundary.bytes().all(valid_char) {
+        return invalid("invalid char");
+    }
+    if boundary.ends_with(' ') {
+        return invalid("ends with a space");
+    }
+    Ok(())
+}
+
+/// reject boundary alphabets that can't make a valid boundary, or none at all
+fn check_alphabet(alphabet: &str) -> io::Result<()> {
+    check_boundary(alphabet)?;
+    if alphabet.contains(' ') {
+        return Err(MultipartError::InvalidBoundary {
+            boundary: alphabet.to_string(),
+            reason: "space in alphabet".to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// decode `%XX` escapes, leaving invalid ones as is
+fn percent_decode(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let escaped = bytes
+            .get(idx + 1..idx + 3)
+            .filter(|_| bytes[idx] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                idx += 3;
+            }
+            None => {
+                out.push(bytes[idx]);
+                idx += 1;
+            }
+        }
+    }
+    out
+}
+
+/// append `params` to a media type, keeping their order
+fn ordered_params(content_type: &Mime, params: &[(&str, &str)]) -> io::Result<Mime> {
+    let mut value = content_type.to_string();
+    for (name, param) in params {
+        let token = !param.is_empty()
+            && param
+                .bytes()
+                .all(|b| b.is_ascii_graphic() && !b"()<>@,;:\\\"/[]?=".contains(&b));
+        if token {
+            value.push_str(&format!("; {name}={param}"));
+        } else {
+            let escaped = param.replace('\\', "\\\\").replace('"', "\\\"");
+            value.push_str(&format!("; {name}=\"{escaped}\""));
+        }
+    }
+    value.parse().map_err(|_| {
+        MultipartError::InvalidHeader {
+            name: "Content-Type".to_string(),
+            value,
+        }
+        .into()
+    })
+}
+
+/// a safe file name out of the last path segment of a url
+///
+/// The query and fragment are dropped, the segment is percent-decoded, and
+/// path separators and control chars are replaced by `_`. Falls back to
+/// `download` when the url has no usable segment, e.g. `https://host/`.
+pub fn filename_from_url(url: &str) -> String {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/').map_or("", |(_, path)| path),
+        None => url,
+    };
+    let segment = path.rsplit('/').find(|segment| !segment.is_empty());
+    let filename: String = String::from_utf8_lossy(&percent_decode(segment.unwrap_or_default()))
+        .chars()
+        .map(|c| {
+            if c == '/' || c == '\\' || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    match filename.trim() {
+        "" | "." | ".." => "download".to_string(),
+        _ => filename,
+    }
+}
+
+fn remote_status_error(url: &str, status: u16) -> io::Error {
+    MultipartError::UnexpectedStatus {
+        url: url.to_string(),
+        status,
+    }
+    .into()
+}
+
+/// seconds needed to upload `content_length` bytes at `bytes_per_sec`
+///
+/// Protocol overhead, i.e. the request line and headers, TCP and TLS framing,
+/// is ignored. Infinite when `bytes_per_sec` is zero, even for an empty body.
+pub fn estimate_upload_secs(content_length: u64, bytes_per_sec: u64) -> f64 {
+    if bytes_per_sec == 0 {
+        return f64::INFINITY;
+    }
+    content_length as f64 / bytes_per_sec as f64
+}
+
+/// bytes sent for `body_len` bytes in HTTP/1.1 chunks of `chunk_size`: each
+/// chunk has its hex size and two CRLFs, and a last empty chunk ends the body
+fn chunked_wire_size(body_len: u64, chunk_size: u64) -> u64 {
+    let framing = |len: u64| format!("{len:x}").len() as u64 + 4;
+    let full = body_len / chunk_size;
+    let rest = body_len % chunk_size;
+    let mut size = body_len + full * framing(chunk_size) + framing(0);
+    if rest > 0 {
+        size += framing(rest);
+    }
+    size
+}
+
+/// formatting of the `Content-Disposition` part header parameters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DispositionStyle {
+    /// a single space after each semicolon, `form-data; name="a"; filename="b"`,
+    /// like browsers do
+    #[default]
+    Spaced,
+    /// no space after semicolons, `form-data;name="a";filename="b"`
+    Compact,
+}
+
+impl DispositionStyle {
+    fn separator(self) -> &'static str {
+        match self {
+            DispositionStyle::Spaced => "; ",
+            DispositionStyle::Compact => ";",
+        }
+    }
+}
+
+/// formatting presets matching what known parsers expect
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParserProfile {
+    /// RFC 7578 framing, the builder defaults
+    #[default]
+    Strict,
+    /// what browsers send, a `----WebKitFormBoundary` boundary followed by 16
+    /// letters and digits
+    Browser,
+    /// compact `Content-Disposition` parameters, and empty parts without the
+    /// blank line after their headers, for old CGI style parsers
+    Legacy,
+}
+
+/// what to do when a part has a content type but no filename
+///
+/// Such a part is sent with a `Content-Type`, but many servers only treat
+/// parts with a filename as files, and take it for a text field. Variants
+/// depend on the enabled features, matches need a wildcard arm.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum MissingFilenamePolicy {
+    /// add the part as is
+    #[default]
+    Allow,
+    /// add the part, and log a warning, needs the `log` feature
+    #[cfg(feature = "log")]
+    Warn,
+    /// fail to add the part
+    Error,
+}
+
+/// how extra part headers set more than once for a part are written
+///
+/// Header names are compared case insensitively.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DuplicateHeaderPolicy {
+    /// write every header, in the order they were added
+    #[default]
+    EmitAll,
+    /// write a single header, where the first one was, with the values
+    /// joined by `, `
+    Merge,
+    /// fail to produce the body
+    Reject,
+}
+
+/// what a part carries, see [`MultipartBuilder::add_part`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartKind {
+    /// a plain text field, without filename nor content type
+    Text(String),
+    /// a file, the content type defaults to `application/octet-stream`
+    File {
+        filename: Option<String>,
+        content_type: Option<Mime>,
+        data: Vec<u8>,
+    },
+}
+
+/// a single part of the body
+#[derive(Debug)]
+struct Part {
+    name: String,
+    filename: Option<String>,
+    content_type: Option<Mime>,
+    headers: Vec<(String, String)>,
+    transfer_encoding: Option<TransferEncoding>,
+    id: Option<String>,
+    /// canonical position, see [`MultipartBuilder::add_text_keyed`]
+    order_key: Option<String>,
+    body: Body,
+}
+
+impl Part {
+    /// whether the part carries a file rather than a plain text field
+    fn is_file(&self) -> bool {
+        self.filename.is_some() || self.content_type.is_some()
+    }
+}
+
+/// where the data of a part comes from
+enum Body {
+    /// in memory data
+    Bytes(Vec<u8>),
+    /// a file, opened when the body is read
+    File(PathBuf),
+    /// a stream that can only be read once, when the body is read, and its
+    /// length if known
+    Reader(Box<dyn Read + Send>, Option<u64>),
+    /// a nested multipart body
+    Nested(Box<MultipartBuilder>),
+    /// a range of a memory mapped file
+    #[cfg(feature = "mmap")]
+    Mmap(mmap::MappedRange),
+    /// a text field value to be filled later
+    Placeholder,
+}
+
+impl Body {
+    /// length of the data, when known without reading it
+    fn known_len(&self) -> io::Result<Option<u64>> {
+        Ok(match self {
+            Body::Bytes(bytes) => Some(bytes.len() as u64),
+            Body::File(path) => Some(fs::metadata(path)?.len()),
+            Body::Reader(_, len) => *len,
+            Body::Nested(builder) => builder.final_length()?,
+            #[cfg(feature = "mmap")]
+            Body::Mmap(range) => Some(range.as_ref().len() as u64),
+            Body::Placeholder => None,
+        })
+    }
+    /// whether the in memory data contains `needle`
+    fn contains(&self, needle: &[u8]) -> bool {
+        match self {
+            Body::Bytes(bytes) => contains(bytes, needle),
+            Body::Nested(builder) => {
+                contains(builder.delimiter().as_bytes(), needle)
+                    || builder.parts.iter().any(|part| part.body.contains(needle))
+            }
+            #[cfg(feature = "mmap")]
+            Body::Mmap(range) => contains(range.as_ref(), needle),
+            Body::File(_) | Body::Reader(..) | Body::Placeholder => false,
+        }
+    }
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Body::Bytes(bytes) => f.debug_tuple("Bytes").field(&bytes.len()).finish(),
+            Body::File(path) => f.debug_tuple("File").field(path).finish(),
+            Body::Reader(_, len) => f.debug_tuple("Reader").field(len).finish(),
+            Body::Nested(builder) => f.debug_tuple("Nested").field(builder).finish(),
+            #[cfg(feature = "mmap")]
+            Body::Mmap(range) => f.debug_tuple("Mmap").field(&range.as_ref().len()).finish(),
+            Body::Placeholder => f.write_str("Placeholder"),
+        }
+    }
+}
+
 /// multipart data build
 #[derive(Debug)]
 pub struct MultipartBuilder {
+    boundary_prefix: String,
     boundary: String,
-    inner: Vec<u8>,
-    data_written: bool,
+    boundary_alphabet: Vec<u8>,
+    boundary_source: SourceBox,
+    boundary_fixed: bool,
+    boundary_from_names: bool,
+    max_boundary_attempts: usize,
+    parts: Vec<Part>,
+    compact_empty_parts: bool,
+    disposition_style: DispositionStyle,
+    pad_to: Option<usize>,
+    max_nesting_depth: usize,
+    default_part_headers: Vec<(String, String)>,
+    default_transfer_encoding: Option<TransferEncoding>,
+    part_ids: bool,
+    default_content_type: Option<Mime>,
+    missing_filename: MissingFilenamePolicy,
+    fold_headers_at: Option<usize>,
+    duplicate_headers: DuplicateHeaderPolicy,
+    escape_names: bool,
+    symlink_mode: SymlinkMode,
+    filename_encoding: FilenameEncoding,
+    header_rewriter: Option<RewriterBox>,
+    max_filename_len: Option<usize>,
+    open_attempts: usize,
+    open_backoff: Duration,
+    file_check: FileCheckBox,
 }
 impl Default for MultipartBuilder {
     fn default() -> Self {
@@ -77,110 +558,1027 @@ impl Default for MultipartBuilder {
 impl MultipartBuilder {
     pub fn new() -> Self {
         Self {
-            boundary: random_alphanumeric(BOUNDARY_LEN),
-            inner: Vec::new(),
-            data_written: false,
+            boundary_prefix: BOUNDARY_PREFIX.to_string(),
+            boundary: random_boundary(BOUNDARY_ALPHABET.as_bytes(), BOUNDARY_LEN),
+            boundary_alphabet: BOUNDARY_ALPHABET.as_bytes().to_vec(),
+            boundary_source: SourceBox(Box::new(RandomBoundary)),
+            boundary_fixed: false,
+            boundary_from_names: false,
+            max_boundary_attempts: MAX_BOUNDARY_ATTEMPTS,
+            parts: Vec::new(),
+            compact_empty_parts: false,
+            disposition_style: DispositionStyle::default(),
+            pad_to: None,
+            max_nesting_depth: MAX_NESTING_DEPTH,
+            default_part_headers: Vec::new(),
+            default_transfer_encoding: None,
+            part_ids: false,
+            default_content_type: None,
+            missing_filename: MissingFilenamePolicy::default(),
+            fold_headers_at: None,
+            duplicate_headers: DuplicateHeaderPolicy::default(),
+            escape_names: false,
+            symlink_mode: SymlinkMode::default(),
+            filename_encoding: FilenameEncoding::default(),
+            header_rewriter: None,
+            max_filename_len: None,
+            open_attempts: 1,
+            open_backoff: Duration::ZERO,
+            file_check: FileCheckBox(Arc::new(RealFs)),
+        }
+    }
+    /// use a fixed boundary instead of the generated one
+    ///
+    /// The boundary is used as is, without the dash prefix, and must be valid
+    /// per RFC 2046: 1 to 70 chars out of letters, digits and `'()+_,-./:=? `,
+    /// not ending with a space. A fixed boundary is never regenerated, adding
+    /// data that contains it fails.
+    pub fn with_boundary(mut self, boundary: &str) -> io::Result<Self> {
+        check_boundary(boundary)?;
+        self.boundary_prefix.clear();
+        self.boundary = boundary.to_string();
+        self.boundary_fixed = true;
+        self.boundary_from_names = false;
+        self.avoid_collisions()?;
+        Ok(self)
+    }
+    /// regenerate the random part of the boundary with `len` chars
+    ///
+    /// The dash prefix counts against the 70 chars limit.
+    pub fn with_boundary_len(mut self, len: usize) -> io::Result<Self> {
+        self.boundary = self.next_boundary(len)?;
+        self.boundary_fixed = false;
+        self.avoid_collisions()?;
+        Ok(self)
+    }
+    /// replace the dashes preceding the random part of the boundary
+    ///
+    /// The random part counts against the 70 chars limit.
+    pub fn with_boundary_prefix(mut self, prefix: &str) -> io::Result<Self> {
+        check_boundary(&format!("{prefix}{}", self.boundary))?;
+        self.boundary_prefix = prefix.to_string();
+        self.avoid_collisions()?;
+        Ok(self)
+    }
+    /// chars the random part of the boundary is drawn from, digits by default
+    ///
+    /// The random part is regenerated with the same length. The chars must be
+    /// allowed in a boundary, spaces aren't.
+    pub fn with_boundary_alphabet(mut self, alphabet: &str) -> io::Result<Self> {
+        check_alphabet(alphabet)?;
+        self.boundary_alphabet = alphabet.as_bytes().to_vec();
+        self.boundary = self.next_boundary(self.boundary.len())?;
+        self.boundary_fixed = false;
+        self.avoid_collisions()?;
+        Ok(self)
+    }
+    /// how many boundaries to try when data collides with the boundary
+    ///
+    /// In memory data is checked as it's added, and when the boundary changes.
+    /// On a collision a new random boundary is drawn, up to `attempts` times
+    /// before giving up, 8 by default. Files and streams aren't checked, they
+    /// are only read when the body is produced.
+    pub fn with_max_boundary_attempts(mut self, attempts: usize) -> Self {
+        self.max_boundary_attempts = attempts;
+        self
+    }
+    /// omit the blank line after the headers of empty parts
+    ///
+    /// By default an empty part is framed like any other, i.e. its headers are
+    /// followed by a blank line and zero bytes of data. With this option the
+    /// part ends right after its last header line, which RFC 2046 also allows
+    /// and which some minimal parsers expect.
+    pub fn with_compact_empty_parts(mut self, compact: bool) -> Self {
+        self.compact_empty_parts = compact;
+        self
+    }
+    /// how the `Content-Disposition` parameters are separated
+    pub fn with_disposition_style(mut self, style: DispositionStyle) -> Self {
+        self.disposition_style = style;
+        self
+    }
+    /// content type of the parts that have none, e.g. `text/plain; charset=utf-8`
+    ///
+    /// See [`merged_content_type`](Self::merged_content_type) for how it
+    /// combines with the content type of a part.
+    pub fn with_default_content_type(mut self, content_type: Mime) -> Self {
+        self.default_content_type = Some(content_type);
+        self
+    }
+    /// the content type emitted for a part with `content_type`
+    ///
+    /// * a part without content type, e.g. a text field, gets the default
+    /// * a part content type always wins over the default, but when both have
+    ///   the same top level type, e.g. `text`, the default parameters the part
+    ///   doesn't set are appended, so a default `charset` carries over
+    /// * without a default, the part content type is used as is
+    pub fn merged_content_type(&self, content_type: Option<&Mime>) -> Option<Mime> {
+        let Some(default) = &self.default_content_type else {
+            return content_type.cloned();
+        };
+        let Some(content_type) = content_type else {
+            return Some(default.clone());
+        };
+        if content_type.type_() != default.type_() {
+            return Some(content_type.clone());
+        }
+        let missing: Vec<_> = default
+            .params()
+            .filter(|(name, _)| content_type.get_param(name.as_str()).is_none())
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        // the parameters come from valid content types, merging can't fail
+        Some(ordered_params(content_type, &missing).unwrap_or_else(|_| content_type.clone()))
+    }
+    /// what to do when a part with a content type but no filename is added
+    ///
+    /// Nested multipart bodies are never checked, they have no filename.
+    pub fn with_missing_filename_policy(mut self, policy: MissingFilenamePolicy) -> Self {
+        self.missing_filename = policy;
+        self
+    }
+    /// fold part headers longer than `width` chars, `None` to never fold
+    ///
+    /// RFC 5322 folding breaks long header lines at spaces into continuation
+    /// lines, starting with that space. It's obsolete in HTTP, so headers are
+    /// not folded by default, but some consumers of email derived bodies
+    /// expect it, usually with a width of 78. Headers without spaces, e.g.
+    /// with the compact disposition style, can't be folded much.
+    pub fn with_header_folding(mut self, width: Option<usize>) -> Self {
+        self.fold_headers_at = width;
+        self
+    }
+    /// how extra headers set more than once for a part are written
+    pub fn with_duplicate_header_policy(mut self, policy: DuplicateHeaderPolicy) -> Self {
+        self.duplicate_headers = policy;
+        self
+    }
+    /// escape quotes and line breaks in field names and filenames
+    ///
+    /// Off by default, names are written as is. With escaping, like browsers
+    /// they are written as `%22`, `%0D` and `%0A`. Without it, such names
+    /// break the part framing.
+    pub fn with_name_escaping(mut self, escape: bool) -> Self {
+        self.escape_names = escape;
+        self
+    }
+    /// cut the filenames of parts added from now on to `max` bytes
+    ///
+    /// The extension is kept when shorter than `max`, e.g. `report.pdf` cut to
+    /// 8 bytes is `repo.pdf`, and multi-byte chars aren't split. Filenames
+    /// aren't cut by default, see [`filename`](Self::filename) for the one
+    /// sent.
+    pub fn with_max_filename_len(mut self, max: usize) -> Self {
+        self.max_filename_len = Some(max);
+        self
+    }
+    /// the filename sent for the last part called `name`, if it has one
+    pub fn filename(&self, name: &str) -> Option<&str> {
+        self.parts
+            .iter()
+            .rev()
+            .find(|part| part.name == name)?
+            .filename
+            .as_deref()
+    }
+    /// a field name or filename as written in the `Content-Disposition`
+    fn disposition_value<'a>(&self, value: &'a str) -> std::borrow::Cow<'a, str> {
+        if self.escape_names && value.contains(['"', '\r', '\n']) {
+            value
+                .replace('"', "%22")
+                .replace('\r', "%0D")
+                .replace('\n', "%0A")
+                .into()
+        } else {
+            value.into()
+        }
+    }
+    /// add an `id` parameter to the `Content-Disposition` of every part
+    ///
+    /// The id is the zero based position of the part in the body, unless one
+    /// was set with [`with_part_id`](Self::with_part_id). Off by default.
+    pub fn with_part_ids(mut self, enabled: bool) -> Self {
+        self.part_ids = enabled;
+        self
+    }
+    /// the `id` disposition parameter of the parts called `name` added so far
+    ///
+    /// It is emitted even without [`with_part_ids`](Self::with_part_ids). The
+    /// id is quoted, so it can't hold quotes nor line breaks.
+    pub fn with_part_id(mut self, name: &str, id: &str) -> io::Result<Self> {
+        if id.contains(['"', '\r', '\n']) {
+            return Err(MultipartError::InvalidHeader {
+                name: "Content-Disposition".to_string(),
+                value: format!("id=\"{id}\""),
+            }
+            .into());
         }
+        for part in self.parts.iter_mut().filter(|part| part.name == name) {
+            part.id = Some(id.to_string());
+        }
+        Ok(self)
+    }
+    /// set the formatting options to a preset
+    ///
+    /// This sets the [disposition style](Self::with_disposition_style),
+    /// [compact empty parts](Self::with_compact_empty_parts) and the boundary
+    /// shape, later calls can still change them one by one. A fixed boundary
+    /// is replaced by a random one.
+    pub fn with_parser_profile(self, profile: ParserProfile) -> io::Result<Self> {
+        let (prefix, alphabet, len) = match profile {
+            ParserProfile::Browser => ("----WebKitFormBoundary", ALPHANUMERIC, 16),
+            ParserProfile::Strict | ParserProfile::Legacy => {
+                (BOUNDARY_PREFIX, BOUNDARY_ALPHABET, BOUNDARY_LEN)
+            }
+        };
+        let legacy = profile == ParserProfile::Legacy;
+        let style = if legacy {
+            DispositionStyle::Compact
+        } else {
+            DispositionStyle::Spaced
+        };
+        let mut builder = self
+            .with_disposition_style(style)
+            .with_compact_empty_parts(legacy);
+        // the prefix is checked with the current random part, which may be
+        // longer than the new one
+        builder.boundary_prefix.clear();
+        builder
+            .with_boundary_alphabet(alphabet)?
+            .with_boundary_len(len)?
+            .with_boundary_prefix(prefix)
+    }
+    /// pad the body to exactly `target_size` bytes
+    ///
+    /// The padding is made of spaces, written after the closing delimiter.
+    /// That is the epilogue of the body, which parsers ignore. Producing the
+    /// body fails if it is already larger than the target, or if a stream of
+    /// unknown length was added.
+    pub fn pad_to(mut self, target_size: usize) -> Self {
+        self.pad_to = Some(target_size);
+        self
+    }
+    /// headers emitted on every part, after the standard ones
+    ///
+    /// A header passed for a single part, e.g. with
+    /// [`add_text_with_headers`](Self::add_text_with_headers), replaces the
+    /// default of the same name (case insensitive) for that part; other part
+    /// headers are emitted after the defaults.
+    pub fn with_default_part_headers(mut self, headers: &[(&str, &str)]) -> Self {
+        self.default_part_headers = headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        self
     }
     /// add text field
     ///
     /// * name field name
     /// * text field text value
-    pub fn add_text(mut self, name: &str, text: &str) -> io::Result<Self> {
-        self.write_field_headers(name, None, None)?;
-        self.inner.write_all(text.as_bytes())?;
+    pub fn add_text(self, name: &str, text: &str) -> io::Result<Self> {
+        self.add_part(name, PartKind::Text(text.to_string()))
+    }
+    /// add text field at a canonical position, e.g. for a signature over
+    /// ordered parts
+    ///
+    /// * order_key the parts added this way are sorted by it, parts with
+    ///   equal keys keep the order they were added in
+    /// * name field name
+    /// * text field text value
+    ///
+    /// The part goes before the first keyed part with a greater key, parts
+    /// added without a key keep their place.
+    pub fn add_text_keyed(self, order_key: &str, name: &str, text: &str) -> io::Result<Self> {
+        let mut builder = self.add_text(name, text)?;
+        let mut part = builder.parts.pop().expect("part just added");
+        let idx = builder
+            .parts
+            .iter()
+            .position(|part| matches!(&part.order_key, Some(key) if key.as_str() > order_key))
+            .unwrap_or(builder.parts.len());
+        part.order_key = Some(order_key.to_string());
+        builder.parts.insert(idx, part);
+        // a boundary derived from the names depends on their order
+        builder.avoid_collisions()?;
+        Ok(builder)
+    }
+    /// add text field from raw bytes, e.g. text in a legacy encoding
+    ///
+    /// * name field name
+    /// * text field value, not checked, see
+    ///   [`validate_text_utf8`](Self::validate_text_utf8)
+    pub fn add_text_bytes(self, name: &str, text: &[u8]) -> io::Result<Self> {
+        self.push_part(name, None, None, &[], Body::Bytes(text.to_vec()))
+    }
+    /// add a text field whose value is set later with [`fill`](Self::fill)
+    ///
+    /// e.g. for a value depending on a server response. Producing the body
+    /// fails while the field isn't filled.
+    pub fn reserve_text(self, name: &str) -> io::Result<Self> {
+        self.push_part(name, None, None, &[], Body::Placeholder)
+    }
+    /// set the value of the first unfilled field [reserved](Self::reserve_text)
+    /// as `name`
+    ///
+    /// Fails when there is no such field.
+    pub fn fill(mut self, name: &str, value: &str) -> io::Result<Self> {
+        let idx = self
+            .parts
+            .iter()
+            .position(|part| part.name == name && matches!(part.body, Body::Placeholder))
+            .ok_or_else(|| MultipartError::NoPlaceholder {
+                name: name.to_string(),
+            })?;
+        self.parts[idx].body = Body::Bytes(value.as_bytes().to_vec());
+        if self.collides(&self.parts[idx..=idx]) {
+            self.avoid_collisions()?;
+        }
         Ok(self)
     }
+    /// add a text field or a file, decided at runtime
+    ///
+    /// * name field name
+    /// * kind the part data and metadata
+    pub fn add_part(self, name: &str, kind: PartKind) -> io::Result<Self> {
+        match kind {
+            PartKind::Text(text) => {
+                self.push_part(name, None, None, &[], Body::Bytes(text.into_bytes()))
+            }
+            PartKind::File {
+                filename,
+                content_type,
+                data,
+            } => {
+                // This is necessary to make sure it is interpreted as a file on the server end.
+                let content_type = Some(content_type.unwrap_or(mime::APPLICATION_OCTET_STREAM));
+                self.push_part(
+                    name,
+                    filename.as_deref(),
+                    content_type,
+                    &[],
+                    Body::Bytes(data),
+                )
+            }
+        }
+    }
+    /// add text field with extra part headers
+    ///
+    /// * name field name
+    /// * text field text value
+    /// * headers extra headers for this part
+    pub fn add_text_with_headers(
+        self,
+        name: &str,
+        text: &str,
+        headers: &[(&str, &str)],
+    ) -> io::Result<Self> {
+        let body = Body::Bytes(text.as_bytes().to_vec());
+        self.push_part(name, None, None, headers, body)
+    }
     /// add file
     ///
     /// * name file field name
     /// * path the sending file path
+    ///
+    /// The file is opened right away to report errors early, but only read
+    /// when the body is produced. A symlink is handled per the
+    /// [mode](Self::with_symlink_mode).
     pub fn add_file<P: AsRef<Path>>(self, name: &str, path: P) -> io::Result<Self> {
         let path = path.as_ref();
         let (content_type, filename) = mime_filename(path);
-        let mut file = File::open(path)?;
-        self.add_stream(&mut file, name, filename, Some(content_type))
+        self.add_path(name, path, filename, content_type)
+    }
+    /// add a transformed copy of a file, e.g. with normalized line endings
+    ///
+    /// * name file field name
+    /// * path the file, read right away
+    /// * transform applied to the file content
+    ///
+    /// The filename and content type are the file's.
+    pub fn add_file_transformed<P, F>(self, name: &str, path: P, transform: F) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+        F: Fn(Vec<u8>) -> Vec<u8>,
+    {
+        let path = path.as_ref();
+        let (content_type, filename) = mime_filename(path);
+        let body = Body::Bytes(transform(fs::read(path)?));
+        self.push_part(name, filename, Some(content_type), &[], body)
     }
     /// add some stream
     pub fn add_stream<S: Read>(
-        mut self,
+        self,
+        stream: &mut S,
+        name: &str,
+        filename: Option<&str>,
+        content_type: Option<Mime>,
+    ) -> io::Result<Self> {
+        self.add_stream_with_headers(stream, name, filename, content_type, &[])
+    }
+    /// add some stream with extra part headers
+    pub fn add_stream_with_headers<S: Read>(
+        self,
         stream: &mut S,
         name: &str,
         filename: Option<&str>,
         content_type: Option<Mime>,
+        headers: &[(&str, &str)],
     ) -> io::Result<Self> {
         // This is necessary to make sure it is interpreted as a file on the server end.
         let content_type = Some(content_type.unwrap_or(mime::APPLICATION_OCTET_STREAM));
-        self.write_field_headers(name, filename, content_type)?;
-        io::copy(stream, &mut self.inner)?;
-        Ok(self)
+        let mut body = Vec::new();
+        io::copy(stream, &mut body)?;
+        self.push_part(name, filename, content_type, headers, Body::Bytes(body))
+    }
+    /// add in memory data
+    ///
+    /// * name field name
+    /// * filename optional file name
+    /// * content_type defaults to `application/octet-stream`
+    /// * bytes the data
+    pub fn add_bytes(
+        self,
+        name: &str,
+        filename: Option<&str>,
+        content_type: Option<Mime>,
+        bytes: &[u8],
+    ) -> io::Result<Self> {
+        let kind = PartKind::File {
+            filename: filename.map(str::to_string),
+            content_type,
+            data: bytes.to_vec(),
+        };
+        self.add_part(name, kind)
+    }
+    /// add owned in memory data, without copying it
+    ///
+    /// * name field name
+    /// * filename optional file name
+    /// * content_type defaults to `application/octet-stream`
+    /// * data the data, moved into the part until the body is produced
+    pub fn add_vec(
+        self,
+        name: &str,
+        filename: Option<&str>,
+        content_type: Option<Mime>,
+        data: Vec<u8>,
+    ) -> io::Result<Self> {
+        let kind = PartKind::File {
+            filename: filename.map(str::to_string),
+            content_type,
+            data,
+        };
+        self.add_part(name, kind)
+    }
+    /// add in memory data, with content type parameters in a given order
+    ///
+    /// * name field name
+    /// * filename optional file name
+    /// * content_type the media type, any parameters it has come first
+    /// * params extra parameters, emitted in this order
+    /// * bytes the data
+    ///
+    /// Values that aren't tokens are quoted. Useful when the header bytes are
+    /// signed, or for servers expecting e.g. `charset` before other parameters.
+    pub fn add_bytes_with_params(
+        self,
+        name: &str,
+        filename: Option<&str>,
+        content_type: Mime,
+        params: &[(&str, &str)],
+        bytes: &[u8],
+    ) -> io::Result<Self> {
+        let content_type = ordered_params(&content_type, params)?;
+        let body = Body::Bytes(bytes.to_vec());
+        self.push_part(name, filename, Some(content_type), &[], body)
+    }
+    /// add a file part with no data
+    ///
+    /// * name field name
+    /// * filename file name
+    /// * content_type defaults to `application/octet-stream`
+    ///
+    /// The part headers are followed by an empty body, see also
+    /// [`with_compact_empty_parts`](Self::with_compact_empty_parts).
+    pub fn add_empty_file(
+        self,
+        name: &str,
+        filename: &str,
+        content_type: Option<Mime>,
+    ) -> io::Result<Self> {
+        self.add_bytes(name, Some(filename), content_type, &[])
+    }
+    /// add the bytes yielded by an iterator
+    ///
+    /// * name field name
+    /// * filename optional file name
+    /// * content_type defaults to `application/octet-stream`
+    /// * iter the data, collected right away
+    pub fn add_byte_iter<I: IntoIterator<Item = u8>>(
+        self,
+        name: &str,
+        filename: Option<&str>,
+        content_type: Option<Mime>,
+        iter: I,
+    ) -> io::Result<Self> {
+        let content_type = Some(content_type.unwrap_or(mime::APPLICATION_OCTET_STREAM));
+        let body = Body::Bytes(iter.into_iter().collect());
+        self.push_part(name, filename, content_type, &[], body)
+    }
+    /// add a stream read only when the body is produced
+    ///
+    /// * name field name
+    /// * filename optional file name
+    /// * content_type defaults to `application/octet-stream`
+    /// * reader the data, read once
+    ///
+    /// The stream can't be read twice, a body holding one can't be retried.
+    pub fn add_reader<R: Read + Send + 'static>(
+        self,
+        name: &str,
+        filename: Option<&str>,
+        content_type: Option<Mime>,
+        reader: R,
+    ) -> io::Result<Self> {
+        let content_type = Some(content_type.unwrap_or(mime::APPLICATION_OCTET_STREAM));
+        let body = Body::Reader(Box::new(reader), None);
+        self.push_part(name, filename, content_type, &[], body)
+    }
+    /// add standard input, read up to its end right away
+    ///
+    /// * name field name
+    /// * filename optional file name
+    /// * content_type defaults to `application/octet-stream`
+    /// * max_bytes the most that is read, input of exactly `max_bytes` is
+    ///   fine, one byte more fails
+    ///
+    /// At most `max_bytes + 1` bytes are held in memory, whatever the size of
+    /// the input.
+    pub fn add_stdin_limited(
+        self,
+        name: &str,
+        filename: Option<&str>,
+        content_type: Option<Mime>,
+        max_bytes: u64,
+    ) -> io::Result<Self> {
+        self.add_read_limited(name, filename, content_type, io::stdin().lock(), max_bytes)
     }
-    fn write_boundary(&mut self) -> io::Result<()> {
-        if self.data_written {
-            self.inner.write_all(b"\r\n")?;
+    fn add_read_limited<R: Read>(
+        self,
+        name: &str,
+        filename: Option<&str>,
+        content_type: Option<Mime>,
+        reader: R,
+        max_bytes: u64,
+    ) -> io::Result<Self> {
+        let mut data = Vec::new();
+        reader
+            .take(max_bytes.saturating_add(1))
+            .read_to_end(&mut data)?;
+        if data.len() as u64 > max_bytes {
+            return Err(MultipartError::PartTooLarge {
+                name: name.to_string(),
+                max: max_bytes,
+            }
+            .into());
         }
-
-        write!(
-            self.inner,
-            "-----------------------------{}\r\n",
-            self.boundary
-        )
+        let content_type = Some(content_type.unwrap_or(mime::APPLICATION_OCTET_STREAM));
+        self.push_part(name, filename, content_type, &[], Body::Bytes(data))
+    }
+    /// add exactly `len` bytes of a stream, read when the body is produced
+    ///
+    /// * name field name
+    /// * filename optional file name
+    /// * content_type defaults to `application/octet-stream`
+    /// * reader the data, read once, in as many reads as it takes
+    /// * len the number of bytes to read, the rest of the stream is left
+    ///
+    /// Unlike [`add_reader`](Self::add_reader) the part length is known, so
+    /// the [body length](Self::final_length) is too. Producing the body fails
+    /// if the stream ends before `len` bytes.
+    pub fn add_sized_reader<R: Read + Send + 'static>(
+        self,
+        name: &str,
+        filename: Option<&str>,
+        content_type: Option<Mime>,
+        reader: R,
+        len: u64,
+    ) -> io::Result<Self> {
+        let content_type = Some(content_type.unwrap_or(mime::APPLICATION_OCTET_STREAM));
+        let reader = ExactReader::new(reader, len);
+        let body = Body::Reader(Box::new(reader), Some(len));
+        self.push_part(name, filename, content_type, &[], body)
     }
-    fn write_field_headers(
-        &mut self,
+    /// add chunks produced by a closure when the body is produced
+    ///
+    /// * name field name
+    /// * filename optional file name
+    /// * content_type defaults to `application/octet-stream`
+    /// * gen called for the next chunk until it returns `None`
+    ///
+    /// Like [`add_reader`](Self::add_reader), the part can only be read once.
+    pub fn add_generator<F>(
+        self,
         name: &str,
         filename: Option<&str>,
         content_type: Option<Mime>,
-    ) -> io::Result<()> {
-        self.write_boundary()?;
-        if !self.data_written {
-            self.data_written = true;
+        gen: F,
+    ) -> io::Result<Self>
+    where
+        F: FnMut() -> Option<Vec<u8>> + Send + 'static,
+    {
+        self.add_reader(name, filename, content_type, GeneratorReader::new(gen))
+    }
+    /// add the body of a remote resource
+    ///
+    /// * name field name
+    /// * url the resource to fetch with a GET request
+    /// * range optional byte range to fetch, sent as a `Range` header
+    ///
+    /// The response is streamed into the part, using its `Content-Type` when
+    /// present, and a file name from the url, see [`filename_from_url`].
+    /// Only `200 OK` is accepted without a range, and only `206 Partial
+    /// Content` with one. The body is only read when the multipart body is
+    /// produced, so it can't be retried, and its length is known when the
+    /// response has a `Content-Length`.
+    pub fn add_remote(self, name: &str, url: &str, range: Option<Range<u64>>) -> io::Result<Self> {
+        let mut req = ureq::get(url);
+        if let Some(range) = &range {
+            if range.is_empty() {
+                return Err(MultipartError::EmptyRange {
+                    start: range.start,
+                    end: range.end,
+                }
+                .into());
+            }
+            req = req.set("Range", &format!("bytes={}-{}", range.start, range.end - 1));
         }
-        write!(
-            self.inner,
-            "Content-Disposition: form-data; name=\"{name}\""
-        )?;
-        if let Some(filename) = filename {
-            write!(self.inner, "; filename=\"{filename}\"")?;
+        let resp = match req.call() {
+            Ok(resp) => resp,
+            Err(Error::Status(status, _)) => return Err(remote_status_error(url, status)),
+            Err(err) => return Err(io::Error::other(err)),
+        };
+        let expected = if range.is_some() { 206 } else { 200 };
+        if resp.status() != expected {
+            return Err(remote_status_error(url, resp.status()));
         }
-        if let Some(content_type) = content_type {
-            write!(self.inner, "\r\nContent-Type: {content_type}")?;
+        let content_type = resp
+            .header("Content-Type")
+            .and_then(|content_type| content_type.parse().ok());
+        let filename = filename_from_url(url);
+        let len = resp
+            .header("Content-Length")
+            .and_then(|len| len.parse().ok());
+        let reader = resp.into_reader();
+        match len {
+            Some(len) => self.add_sized_reader(name, Some(&filename), content_type, reader, len),
+            None => self.add_reader(name, Some(&filename), content_type, reader),
         }
-        self.inner.write_all(b"\r\n\r\n")
     }
-    /// general multipart data
+    /// check that every text field is valid UTF-8
     ///
-    /// # Return
-    /// * (content_type,post_data)
-    ///    * content_type http header content type
-    ///    * post_data ureq.req.send_send_bytes(&post_data)
+    /// File parts aren't checked. Fails on the first invalid field.
+    pub fn validate_text_utf8(&self) -> Result<(), MultipartError> {
+        for part in self.parts.iter().filter(|part| !part.is_file()) {
+            if let Body::Bytes(bytes) = &part.body {
+                if let Err(err) = std::str::from_utf8(bytes) {
+                    return Err(MultipartError::InvalidUtf8 {
+                        name: part.name.clone(),
+                        valid_up_to: err.valid_up_to(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+    /// whether any part is a file, i.e. has a filename or a content type
+    ///
+    /// A body without files may be sent urlencoded instead, see
+    /// [`to_urlencoded_pairs`](Self::to_urlencoded_pairs).
+    pub fn has_files(&self) -> bool {
+        self.parts.iter().any(Part::is_file)
+    }
+    /// an equivalent `curl` command, for debugging
     ///
-    pub fn finish(mut self) -> io::Result<(String, Vec<u8>)> {
-        if self.data_written {
-            self.inner.write_all(b"\r\n")?;
+    /// Text fields are rendered as `--form-string name=value`, files as
+    /// `-F name=@path;type=..`. Parts that only exist in memory or as a
+    /// stream can't be passed to curl, they are listed in `#` comment lines
+    /// before the command. Extra part headers are left out.
+    pub fn to_curl(&self, url: &str) -> String {
+        let mut notes = String::new();
+        let mut cmd = "curl".to_string();
+        for part in &self.parts {
+            let content_type = self.merged_content_type(part.content_type.as_ref());
+            match &part.body {
+                Body::Bytes(bytes) if !part.is_file() => match std::str::from_utf8(bytes) {
+                    Ok(text) => {
+                        let field = format!("{}={text}", part.name);
+                        cmd.push_str(&format!(" --form-string {}", shell_quote(&field)));
+                    }
+                    Err(_) => notes.push_str(&format!(
+                        "# non UTF-8 text field {:?} left out\n",
+                        part.name
+                    )),
+                },
+                Body::File(path) => {
+                    let mut field = format!("{}=@{}", part.name, path.display());
+                    if let Some(content_type) = content_type {
+                        field.push_str(&format!(";type={content_type}"));
+                    }
+                    if part.filename.as_deref() != opt_filename(path) {
+                        let filename = part.filename.as_deref().unwrap_or_default();
+                        field.push_str(&format!(";filename={filename}"));
+                    }
+                    cmd.push_str(&format!(" -F {}", shell_quote(&field)));
+                }
+                _ => notes.push_str(&format!(
+                    "# in memory or streamed part {:?} left out\n",
+                    part.name
+                )),
+            }
         }
-
-        // always write the closing boundary, even for empty bodies
-        write!(
-            self.inner,
-            "-----------------------------{}--\r\n",
-            self.boundary
-        )?;
-        Ok((
-            format!(
-                "multipart/form-data; boundary=---------------------------{}",
-                self.boundary
-            ),
-            self.inner,
-        ))
+        format!("{notes}{cmd} {}", shell_quote(url))
     }
-}
-
+    /// the text fields as key/value pairs, for `ureq::Request::send_form`
+    ///
+    /// Returns `None` when the body holds any file part, or a text field that
+    /// is not valid UTF-8, as those can't be sent urlencoded.
+    pub fn to_urlencoded_pairs(&self) -> Option<Vec<(String, String)>> {
+        self.parts
+            .iter()
+            .map(|part| match &part.body {
+                Body::Bytes(bytes) if !part.is_file() => {
+                    let value = String::from_utf8(bytes.clone()).ok()?;
+                    Some((part.name.clone(), value))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+    fn push_part(
+        mut self,
+        name: &str,
+        filename: Option<&str>,
+        content_type: Option<Mime>,
+        headers: &[(&str, &str)],
+        body: Body,
+    ) -> io::Result<Self> {
+        for (name, value) in headers {
+            check_header(name, value)?;
+        }
+        let nested = matches!(body, Body::Nested(_));
+        if content_type.is_some() && filename.is_none() && !nested {
+            match self.missing_filename {
+                MissingFilenamePolicy::Allow => {}
+                #[cfg(feature = "log")]
+                MissingFilenamePolicy::Warn => {
+                    log::warn!("part {name:?} has a content type but no filename");
+                }
+                MissingFilenamePolicy::Error => {
+                    return Err(MultipartError::MissingFilename {
+                        name: name.to_string(),
+                    }
+                    .into());
+                }
+            }
+        }
+        self.parts.push(Part {
+            name: name.to_string(),
+            filename: filename.map(|filename| match self.max_filename_len {
+                Some(max) => truncate_filename(filename, max),
+                None => filename.to_string(),
+            }),
+            content_type,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            transfer_encoding: None,
+            id: None,
+            order_key: None,
+            body,
+        });
+        if self.boundary_from_names || self.collides(&self.parts[self.parts.len() - 1..]) {
+            self.avoid_collisions()?;
+        }
+        Ok(self)
+    }
+    /// whether `data` contains the current delimiter, i.e. the boundary after
+    /// two dashes, and can't be sent as is
+    ///
+    /// This is the check parts get when added, to insert untrusted data on
+    /// its own. Adding parts may draw a new boundary, check again after.
+    pub fn contains_boundary(&self, data: &[u8]) -> bool {
+        contains(data, self.delimiter().as_bytes())
+    }
+    /// whether any in memory data contains the delimiter
+    fn collides(&self, parts: &[Part]) -> bool {
+        let delimiter = self.delimiter();
+        parts
+            .iter()
+            .any(|part| part.body.contains(delimiter.as_bytes()))
+    }
+    /// the boundary preceded by two dashes, starting every part
+    fn delimiter(&self) -> String {
+        format!("--{}{}", self.boundary_prefix, self.boundary)
+    }
+    /// draw new boundaries until no in memory data contains the delimiter
+    fn avoid_collisions(&mut self) -> io::Result<()> {
+        if self.boundary_from_names {
+            return self.derive_boundary();
+        }
+        let mut attempts = 0;
+        while self.collides(&self.parts) {
+            if self.boundary_fixed || attempts == self.max_boundary_attempts {
+                return Err(MultipartError::BoundaryCollision {
+                    boundary: format!("{}{}", self.boundary_prefix, self.boundary),
+                    attempts,
+                }
+                .into());
+            }
+            self.boundary = self.next_boundary(self.boundary.len())?;
+            attempts += 1;
+        }
+        Ok(())
+    }
+    fn write_part_headers<W: Write>(&self, w: &mut W, idx: usize, part: &Part) -> io::Result<()> {
+        let separator = self.disposition_style.separator();
+        let mut disposition = format!(
+            "Content-Disposition: form-data{separator}name=\"{}\"",
+            self.disposition_value(&part.name)
+        );
+        match &part.filename {
+            Some(filename)
+                if self.filename_encoding == FilenameEncoding::Rfc5987 && !filename.is_ascii() =>
+            {
+                let fallback = rfc5987::ascii_fallback(filename);
+                let fallback = self.disposition_value(&fallback);
+                let extended = rfc5987::ext_value(filename);
+                disposition.push_str(&format!(
+                    "{separator}filename=\"{fallback}\"{separator}filename*={extended}"
+                ));
+            }
+            Some(filename) => {
+                let filename = self.disposition_value(filename);
+                disposition.push_str(&format!("{separator}filename=\"{filename}\""));
+            }
+            None => {}
+        }
+        match &part.id {
+            Some(id) => disposition.push_str(&format!("{separator}id=\"{id}\"")),
+            None if self.part_ids => disposition.push_str(&format!("{separator}id=\"{idx}\"")),
+            None => {}
+        }
+        let mut lines = vec![disposition];
+        let content_type = self.merged_content_type(part.content_type.as_ref());
+        if let Some(content_type) = &content_type {
+            lines.push(format!("Content-Type: {content_type}"));
+        }
+        if let Some(encoding) = self.part_encoding(part) {
+            lines.push(format!("Content-Transfer-Encoding: {encoding}"));
+        }
+        let defaults = self.default_part_headers.iter().filter(|(name, _)| {
+            !part
+                .headers
+                .iter()
+                .any(|(n, _)| n.eq_ignore_ascii_case(name))
+        });
+        let mut headers: Vec<(&str, String)> = Vec::new();
+        for (name, value) in defaults.chain(&part.headers) {
+            check_header(name, value)?;
+            let duplicate = headers
+                .iter_mut()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name));
+            match (self.duplicate_headers, duplicate) {
+                (DuplicateHeaderPolicy::Merge, Some((_, merged))) => {
+                    merged.push_str(", ");
+                    merged.push_str(value);
+                }
+                (DuplicateHeaderPolicy::Reject, Some(_)) => {
+                    return Err(MultipartError::DuplicateHeader {
+                        name: name.to_string(),
+                    }
+                    .into());
+                }
+                _ => headers.push((name, value.clone())),
+            }
+        }
+        let mut headers = PartHeaders {
+            index: idx,
+            name: &part.name,
+            filename: part.filename.as_deref(),
+            content_type: content_type.as_ref(),
+            headers: headers
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        };
+        self.rewrite_headers(&mut headers)?;
+        for (name, value) in headers.headers {
+            lines.push(format!("{name}: {value}"));
+        }
+        write!(w, "{}", self.delimiter())?;
+        for line in lines {
+            match self.fold_headers_at {
+                Some(width) => write!(w, "\r\n{}", fold_header(&line, width))?,
+                None => write!(w, "\r\n{line}")?,
+            }
+        }
+        // an empty part may end right after its last header line, the next
+        // delimiter's leading CRLF then closes it
+        if self.compact_empty_parts && self.part_len(part)? == Some(0) {
+            w.write_all(b"\r\n")
+        } else {
+            w.write_all(b"\r\n\r\n")
+        }
+    }
+    /// general multipart data
+    ///
+    /// # Return
+    /// * (content_type,post_data)
+    ///    * content_type http header content type
+    ///    * post_data ureq.req.send_send_bytes(&post_data)
+    ///
+    pub fn finish(self) -> io::Result<(String, Vec<u8>)> {
+        let (content_type, mut reader) = self.into_reader()?;
+        let mut inner = Vec::new();
+        reader.read_to_end(&mut inner)?;
+        Ok((content_type, inner))
+    }
+    /// time needed to upload the body at `bytes_per_sec`
+    ///
+    /// See [`estimate_upload_secs`], which ignores protocol overhead. `None`
+    /// when the [body length](Self::final_length) is unknown, or the rate is
+    /// zero.
+    pub fn estimated_duration(&self, bytes_per_sec: u64) -> io::Result<Option<Duration>> {
+        if bytes_per_sec == 0 {
+            return Ok(None);
+        }
+        let len = self.final_length()?;
+        Ok(len.map(|len| Duration::from_secs_f64(estimate_upload_secs(len, bytes_per_sec))))
+    }
+    /// bytes sent for the body with chunked transfer encoding, in chunks of
+    /// `chunk_size` bytes
+    ///
+    /// This counts the chunk framing on top of the [body
+    /// length](Self::final_length), not the request headers, and is `None`
+    /// when the length is unknown. Fails when `chunk_size` is 0.
+    pub fn wire_size_chunked(&self, chunk_size: usize) -> io::Result<Option<u64>> {
+        if chunk_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "chunk size must not be 0",
+            ));
+        }
+        let len = self.final_length()?;
+        Ok(len.map(|len| chunked_wire_size(len, chunk_size as u64)))
+    }
+    /// the http header content type the body will be sent with
+    ///
+    /// Adding data that collides with the boundary regenerates it, and so
+    /// changes the content type.
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary_param())
+    }
+    /// the boundary as a header parameter value, quoted when it holds chars
+    /// that aren't allowed in a token
+    fn boundary_param(&self) -> String {
+        let boundary = format!("{}{}", self.boundary_prefix, self.boundary);
+        if boundary.contains(['(', ')', ',', '/', ':', '=', '?', ' ']) {
+            format!("\"{boundary}\"")
+        } else {
+            boundary
+        }
+    }
+    /// the parameters of [`content_type`](Self::content_type), by name
+    pub fn content_type_params(&self) -> BTreeMap<String, String> {
+        let content_type: Mime = self
+            .content_type()
+            .parse()
+            .expect("boundaries are validated");
+        content_type
+            .params()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+}
+
 /// multipart request for ureq
 /// add send_multipart_file/send_multipart_files method to ureq Request
+#[allow(clippy::result_large_err)]
 pub trait MultipartRequest {
     fn send_multipart_files<P: AsRef<Path>>(self, files: &[P]) -> Result<Response, Error>;
     fn send_multipart_file<P: AsRef<Path>>(self, name: &str, file: P) -> Result<Response, Error>;
+    fn send_multipart_retrying(
+        self,
+        body: &RetryableBody,
+        attempts: usize,
+    ) -> Result<Response, Error>;
+    fn send_multipart_with_deadline(
+        self,
+        builder: MultipartBuilder,
+        deadline: Duration,
+    ) -> Result<Response, Error>;
 }
 impl MultipartRequest for Request {
     /// send multi files,auto set the name with file's name by multipart
@@ -203,10 +1601,57 @@ impl MultipartRequest for Request {
         let (content_type, data) = MultipartBuilder::new().add_file(name, path)?.finish()?;
         self.set("Content-Type", &content_type).send_bytes(&data)
     }
+    /// stream a body, sending it again on transient errors
+    ///
+    /// Transport errors and `429`, `502`, `503` and `504` statuses are retried
+    /// right away, up to `attempts` tries in total. Each try reads the body
+    /// again from the start, with files reopened.
+    fn send_multipart_retrying(
+        self,
+        body: &RetryableBody,
+        attempts: usize,
+    ) -> Result<Response, Error> {
+        let mut attempt = 1;
+        loop {
+            let res = self
+                .clone()
+                .set("Content-Type", &body.content_type())
+                .send(body.reader()?);
+            match res {
+                Err(err) if attempt < attempts && is_transient(&err) => attempt += 1,
+                res => return res,
+            }
+        }
+    }
+    /// stream a body, aborting the upload when `deadline` has elapsed
+    ///
+    /// See [`MultipartReader::with_deadline`] for what the deadline covers
+    /// compared to ureq's own timeouts.
+    fn send_multipart_with_deadline(
+        self,
+        builder: MultipartBuilder,
+        deadline: Duration,
+    ) -> Result<Response, Error> {
+        let (content_type, reader) = builder.into_reader()?;
+        self.set("Content-Type", &content_type)
+            .send(reader.with_deadline(deadline))
+    }
+}
+
+fn is_transient(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::Transport(_) | Error::Status(429 | 502 | 503 | 504, _)
+    )
 }
+#[cfg(test)]
+mod test_util;
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::test_util::{response, serve};
+    use std::fs::File;
 
     fn get_file_string(p: &Path) -> String {
         let mut file = File::open(p).unwrap();
@@ -226,12 +1671,1045 @@ mod test {
             .finish()
             .unwrap();
 
-        assert!(data.len() > 0);
+        assert!(!data.is_empty());
         assert!(content_type.contains("multipart/form-data;"));
         let datastr = String::from_utf8(data.clone()).unwrap();
         assert!(datastr.contains(&file_str));
     }
 
+    #[test]
+    fn test_empty_part_framing() {
+        let mut builder = MultipartBuilder::new();
+        builder.boundary = "B".into();
+        let (_, data) = builder.add_text("k", "").unwrap().finish().unwrap();
+        assert_eq!(
+            data,
+            b"-----------------------------B\r\n\
+            Content-Disposition: form-data; name=\"k\"\r\n\
+            \r\n\
+            \r\n\
+            -----------------------------B--\r\n"
+        );
+
+        let mut builder = MultipartBuilder::new().with_compact_empty_parts(true);
+        builder.boundary = "B".into();
+        let (_, data) = builder
+            .add_text("k", "")
+            .unwrap()
+            .add_stream(&mut io::empty(), "f", Some("f.bin"), None)
+            .unwrap()
+            .add_text("v", "x")
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(
+            data,
+            b"-----------------------------B\r\n\
+            Content-Disposition: form-data; name=\"k\"\r\n\
+            \r\n\
+            -----------------------------B\r\n\
+            Content-Disposition: form-data; name=\"f\"; filename=\"f.bin\"\r\n\
+            Content-Type: application/octet-stream\r\n\
+            \r\n\
+            -----------------------------B\r\n\
+            Content-Disposition: form-data; name=\"v\"\r\n\
+            \r\n\
+            x\r\n\
+            -----------------------------B--\r\n"
+        );
+    }
+
+    #[test]
+    fn test_add_remote() {
+        let url = serve(|req, _| {
+            let content = b"abcdefghijklmnopqrstuvwxyz";
+            match (req.head.split(' ').nth(1), req.header("Range")) {
+                (Some("/letters"), None) => {
+                    response(200, &[("Content-Type", "text/plain")], content)
+                }
+                (Some("/letters"), Some(range)) => {
+                    let (start, end) = range
+                        .strip_prefix("bytes=")
+                        .and_then(|range| range.split_once('-'))
+                        .unwrap();
+                    let (start, end): (usize, usize) =
+                        (start.parse().unwrap(), end.parse().unwrap());
+                    response(206, &[], &content[start..=end])
+                }
+                (Some("/ignores-range"), _) => response(200, &[], content),
+                _ => response(404, &[], b"not found"),
+            }
+        });
+
+        let builder = MultipartBuilder::new()
+            .add_remote("full", &format!("{url}/letters"), None)
+            .unwrap()
+            .add_remote("part", &format!("{url}/letters"), Some(2..5))
+            .unwrap();
+        assert!(!builder.is_retryable());
+        assert!(builder.final_length().unwrap().is_some());
+        let (_, data) = builder.finish().unwrap();
+        let datastr = String::from_utf8(data).unwrap();
+        assert!(datastr.contains("Content-Type: text/plain\r\n\r\nabcdefghijklmnopqrstuvwxyz\r\n"));
+        assert!(datastr.contains("Content-Type: application/octet-stream\r\n\r\ncde\r\n"));
+        assert!(datastr.contains("name=\"part\"; filename=\"letters\"\r\n"));
+
+        let err = MultipartBuilder::new()
+            .add_remote("missing", &format!("{url}/missing"), None)
+            .unwrap_err();
+        assert!(err.to_string().contains("unexpected status 404"));
+
+        let err = MultipartBuilder::new()
+            .add_remote("part", &format!("{url}/ignores-range"), Some(0..1))
+            .unwrap_err();
+        assert!(err.to_string().contains("unexpected status 200"));
+
+        let err = MultipartBuilder::new()
+            .add_remote("part", &format!("{url}/letters"), Some(3..3))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_default_part_headers() {
+        let mut builder = MultipartBuilder::new()
+            .with_default_part_headers(&[("X-Tenant", "t1"), ("X-Tag", "a")]);
+        builder.boundary = "B".into();
+        let (_, data) = builder
+            .add_text("k", "v")
+            .unwrap()
+            .add_text_with_headers("l", "w", &[("x-tag", "b"), ("X-Extra", "e")])
+            .unwrap()
+            .add_stream(&mut "x".as_bytes(), "f", Some("f.txt"), None)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "-----------------------------B\r\n\
+            Content-Disposition: form-data; name=\"k\"\r\n\
+            X-Tenant: t1\r\n\
+            X-Tag: a\r\n\
+            \r\n\
+            v\r\n\
+            -----------------------------B\r\n\
+            Content-Disposition: form-data; name=\"l\"\r\n\
+            X-Tenant: t1\r\n\
+            x-tag: b\r\n\
+            X-Extra: e\r\n\
+            \r\n\
+            w\r\n\
+            -----------------------------B\r\n\
+            Content-Disposition: form-data; name=\"f\"; filename=\"f.txt\"\r\n\
+            Content-Type: application/octet-stream\r\n\
+            X-Tenant: t1\r\n\
+            X-Tag: a\r\n\
+            \r\n\
+            x\r\n\
+            -----------------------------B--\r\n"
+        );
+
+        let err = MultipartBuilder::new()
+            .add_text_with_headers("k", "v", &[("X-Bad", "a\r\nb")])
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_urlencoded_pairs() {
+        let pairs = MultipartBuilder::new()
+            .add_text("a", "1")
+            .unwrap()
+            .add_text_with_headers("b", "two words", &[("X-Tag", "t")])
+            .unwrap()
+            .to_urlencoded_pairs()
+            .unwrap();
+        assert_eq!(
+            pairs,
+            vec![
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "two words".to_string())
+            ]
+        );
+
+        let builder = MultipartBuilder::new()
+            .add_text("a", "1")
+            .unwrap()
+            .add_stream(&mut "x".as_bytes(), "f", Some("f.txt"), None)
+            .unwrap();
+        assert_eq!(builder.to_urlencoded_pairs(), None);
+        assert_eq!(MultipartBuilder::new().to_urlencoded_pairs(), Some(vec![]));
+    }
+
+    #[test]
+    fn test_boundary_len_limit() {
+        let max = "b".repeat(MAX_BOUNDARY_LEN);
+        let (content_type, data) = MultipartBuilder::new()
+            .with_boundary(&max)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(content_type, format!("multipart/form-data; boundary={max}"));
+        assert_eq!(data, format!("--{max}--\r\n").into_bytes());
+
+        let invalid_boundary = |err: io::Error| {
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            let err = err
+                .into_inner()
+                .unwrap()
+                .downcast::<MultipartError>()
+                .unwrap();
+            assert!(matches!(*err, MultipartError::InvalidBoundary { .. }));
+        };
+        let too_long = "b".repeat(MAX_BOUNDARY_LEN + 1);
+        invalid_boundary(
+            MultipartBuilder::new()
+                .with_boundary(&too_long)
+                .unwrap_err(),
+        );
+        invalid_boundary(MultipartBuilder::new().with_boundary("").unwrap_err());
+        invalid_boundary(MultipartBuilder::new().with_boundary("a\r\nb").unwrap_err());
+        invalid_boundary(MultipartBuilder::new().with_boundary("ab ").unwrap_err());
+
+        // the default 27 dashes prefix leaves room for 43 random chars
+        let (content_type, _) = MultipartBuilder::new()
+            .with_boundary_len(43)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(
+            content_type.len(),
+            "multipart/form-data; boundary=".len() + 70
+        );
+        invalid_boundary(MultipartBuilder::new().with_boundary_len(44).unwrap_err());
+
+        // the default 29 random chars leave room for a 41 chars prefix
+        let prefix = "-".repeat(41);
+        let (content_type, _) = MultipartBuilder::new()
+            .with_boundary_prefix(&prefix)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert!(content_type.starts_with(&format!("multipart/form-data; boundary={prefix}")));
+        assert_eq!(
+            content_type.len(),
+            "multipart/form-data; boundary=".len() + 70
+        );
+        invalid_boundary(
+            MultipartBuilder::new()
+                .with_boundary_prefix(&"-".repeat(42))
+                .unwrap_err(),
+        );
+    }
+
+    #[test]
+    fn test_boundary_collisions() {
+        let collision = |err: io::Error| {
+            let err = err
+                .into_inner()
+                .unwrap()
+                .downcast::<MultipartError>()
+                .unwrap();
+            match *err {
+                MultipartError::BoundaryCollision { attempts, .. } => attempts,
+                err => panic!("unexpected error {err}"),
+            }
+        };
+
+        // a single char alphabet always draws the same boundary
+        let builder = MultipartBuilder::new()
+            .with_boundary_prefix("")
+            .unwrap()
+            .with_boundary_alphabet("a")
+            .unwrap()
+            .with_boundary_len(3)
+            .unwrap();
+        let err = builder.add_text("k", "x--aaay").unwrap_err();
+        assert_eq!(collision(err), 8);
+
+        let err = MultipartBuilder::new()
+            .with_max_boundary_attempts(2)
+            .with_boundary_prefix("")
+            .unwrap()
+            .with_boundary_alphabet("a")
+            .unwrap()
+            .add_text("k", "--aaaaaaaaaaaaaaaaaaaaaaaaaaaaa")
+            .unwrap_err();
+        assert_eq!(collision(err), 2);
+
+        // a fixed boundary is never regenerated
+        let err = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_bytes("f", None, None, b"\r\n--B--\r\n")
+            .unwrap_err();
+        assert_eq!(collision(err), 0);
+        let err = MultipartBuilder::new()
+            .add_text("k", "--B")
+            .unwrap()
+            .with_boundary("B")
+            .unwrap_err();
+        assert_eq!(collision(err), 0);
+
+        // with two chars the other boundary is found
+        let (content_type, data) = MultipartBuilder::new()
+            .with_max_boundary_attempts(64)
+            .with_boundary_prefix("")
+            .unwrap()
+            .with_boundary_alphabet("ab")
+            .unwrap()
+            .with_boundary_len(1)
+            .unwrap()
+            .add_text("k", "--a")
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(content_type, "multipart/form-data; boundary=b");
+        assert!(data.starts_with(b"--b\r\n"));
+    }
+
+    #[test]
+    fn test_disposition_style() {
+        let disposition = |style| {
+            let (_, data) = MultipartBuilder::new()
+                .with_disposition_style(style)
+                .with_boundary("B")
+                .unwrap()
+                .add_bytes("f", Some("a.bin"), None, b"")
+                .unwrap()
+                .add_text("k", "")
+                .unwrap()
+                .finish()
+                .unwrap();
+            let data = String::from_utf8(data).unwrap();
+            data.lines()
+                .filter(|line| line.starts_with("Content-Disposition"))
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            disposition(DispositionStyle::Spaced),
+            [
+                "Content-Disposition: form-data; name=\"f\"; filename=\"a.bin\"",
+                "Content-Disposition: form-data; name=\"k\"",
+            ]
+        );
+        assert_eq!(
+            disposition(DispositionStyle::Compact),
+            [
+                "Content-Disposition: form-data;name=\"f\";filename=\"a.bin\"",
+                "Content-Disposition: form-data;name=\"k\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_part_ids() {
+        let (_, data) = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .with_part_ids(true)
+            .add_bytes("f", Some("a.bin"), None, b"")
+            .unwrap()
+            .add_text("k", "")
+            .unwrap()
+            .add_text("k", "")
+            .unwrap()
+            .add_text("m", "")
+            .unwrap()
+            .with_part_id("m", "meta")
+            .unwrap()
+            .finish()
+            .unwrap();
+        let data = String::from_utf8(data).unwrap();
+        let dispositions: Vec<_> = data
+            .lines()
+            .filter(|line| line.starts_with("Content-Disposition"))
+            .collect();
+        assert_eq!(
+            dispositions,
+            [
+                "Content-Disposition: form-data; name=\"f\"; filename=\"a.bin\"; id=\"0\"",
+                "Content-Disposition: form-data; name=\"k\"; id=\"1\"",
+                "Content-Disposition: form-data; name=\"k\"; id=\"2\"",
+                "Content-Disposition: form-data; name=\"m\"; id=\"meta\"",
+            ]
+        );
+
+        let (_, data) = MultipartBuilder::new()
+            .add_text("k", "")
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert!(!String::from_utf8(data).unwrap().contains("id="));
+        assert!(MultipartBuilder::new().with_part_id("k", "a\"b").is_err());
+    }
+
+    #[test]
+    fn test_validate_text_utf8() {
+        let builder = MultipartBuilder::new()
+            .add_text_bytes("ok", "caf\u{e9}".as_bytes())
+            .unwrap()
+            .add_bytes("file", None, None, b"\xff")
+            .unwrap();
+        assert_eq!(builder.validate_text_utf8(), Ok(()));
+        let builder = builder.add_text_bytes("latin1", b"caf\xe9").unwrap();
+        assert_eq!(
+            builder.validate_text_utf8(),
+            Err(MultipartError::InvalidUtf8 {
+                name: "latin1".to_string(),
+                valid_up_to: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn test_add_empty_file() {
+        let (_, data) = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_empty_file("touch", "empty.txt", Some(mime::TEXT_PLAIN))
+            .unwrap()
+            .finish()
+            .unwrap();
+        let expected =
+            "--B\r\nContent-Disposition: form-data; name=\"touch\"; filename=\"empty.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\n\r\n--B--\r\n";
+        assert_eq!(String::from_utf8(data).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parser_profiles() {
+        let body = |profile| {
+            let builder = MultipartBuilder::new()
+                .with_boundary("B")
+                .unwrap()
+                .with_parser_profile(profile)
+                .unwrap();
+            let boundary = builder.content_type_params()["boundary"].clone();
+            let (_, data) = builder
+                .add_text("k", "v")
+                .unwrap()
+                .add_bytes("f", Some("a.bin"), None, b"")
+                .unwrap()
+                .finish()
+                .unwrap();
+            (boundary, String::from_utf8(data).unwrap())
+        };
+
+        let spaced = |boundary: &str| {
+            format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"k\"\r\n\r\nv\r\n\
+                --{boundary}\r\nContent-Disposition: form-data; name=\"f\"; filename=\"a.bin\"\r\n\
+                Content-Type: application/octet-stream\r\n\r\n\r\n--{boundary}--\r\n"
+            )
+        };
+        let (boundary, data) = body(ParserProfile::Strict);
+        assert_eq!(boundary.len(), 27 + 29);
+        assert_eq!(data, spaced(&boundary));
+
+        let (boundary, data) = body(ParserProfile::Browser);
+        let random = boundary.strip_prefix("----WebKitFormBoundary").unwrap();
+        assert_eq!(random.len(), 16);
+        assert!(random.bytes().all(|b| b.is_ascii_alphanumeric()));
+        assert_eq!(data, spaced(&boundary));
+
+        let (boundary, data) = body(ParserProfile::Legacy);
+        let expected = format!(
+            "--{boundary}\r\nContent-Disposition: form-data;name=\"k\"\r\n\r\nv\r\n\
+            --{boundary}\r\nContent-Disposition: form-data;name=\"f\";filename=\"a.bin\"\r\n\
+            Content-Type: application/octet-stream\r\n\r\n--{boundary}--\r\n"
+        );
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn test_estimated_duration() {
+        assert_eq!(estimate_upload_secs(1000, 250), 4.0);
+        assert_eq!(estimate_upload_secs(0, 250), 0.0);
+        assert_eq!(estimate_upload_secs(1, 0), f64::INFINITY);
+        assert_eq!(estimate_upload_secs(1000, 0), f64::INFINITY);
+        assert_eq!(estimate_upload_secs(0, 0), f64::INFINITY);
+
+        let builder = MultipartBuilder::new()
+            .add_bytes("f", None, None, &[0; 900])
+            .unwrap();
+        let len = builder.final_length().unwrap().unwrap();
+        assert_eq!(
+            builder.estimated_duration(len / 2).unwrap(),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(builder.estimated_duration(0).unwrap(), None);
+        let builder = builder.add_reader("r", None, None, io::empty()).unwrap();
+        assert_eq!(builder.estimated_duration(1000).unwrap(), None);
+    }
+
+    #[test]
+    fn test_filename_from_url() {
+        let cases = [
+            ("https://host/dir/report.pdf", "report.pdf"),
+            ("https://host/dir/report.pdf?token=a/b#top", "report.pdf"),
+            ("https://host/dir/", "dir"),
+            ("https://host/my%20file%2Fx.txt", "my file_x.txt"),
+            ("https://host/a%0Ab%5C..", "a_b_.."),
+            ("https://host/%2E%2E", "download"),
+            ("https://host", "download"),
+            ("https://host/?q=1", "download"),
+            ("archive.tar.gz", "archive.tar.gz"),
+        ];
+        for (url, filename) in cases {
+            assert_eq!(filename_from_url(url), filename, "{url}");
+        }
+    }
+
+    #[test]
+    fn test_default_content_type() {
+        let builder = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .with_default_content_type(mime::TEXT_PLAIN_UTF_8);
+        let merged = |content_type: &str| {
+            let content_type: Mime = content_type.parse().unwrap();
+            builder
+                .merged_content_type(Some(&content_type))
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            builder.merged_content_type(None),
+            Some(mime::TEXT_PLAIN_UTF_8)
+        );
+        assert_eq!(merged("text/csv"), "text/csv; charset=utf-8");
+        assert_eq!(
+            merged("text/csv; charset=latin1"),
+            "text/csv; charset=latin1"
+        );
+        assert_eq!(
+            merged("text/csv; header=present"),
+            "text/csv; header=present; charset=utf-8"
+        );
+        assert_eq!(merged("image/png"), "image/png");
+        assert_eq!(MultipartBuilder::new().merged_content_type(None), None);
+
+        let (_, data) = builder
+            .add_text("k", "v")
+            .unwrap()
+            .add_bytes("f", None, Some(mime::TEXT_CSV), b"a,b")
+            .unwrap()
+            .finish()
+            .unwrap();
+        let expected = "--B\r\nContent-Disposition: form-data; name=\"k\"\r\n\
+            Content-Type: text/plain; charset=utf-8\r\n\r\nv\r\n\
+            --B\r\nContent-Disposition: form-data; name=\"f\"\r\n\
+            Content-Type: text/csv; charset=utf-8\r\n\r\na,b\r\n--B--\r\n";
+        assert_eq!(String::from_utf8(data).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_missing_filename_policy() {
+        let builder = MultipartBuilder::new()
+            .add_stream(&mut &b"data"[..], "anon", None, None)
+            .unwrap()
+            .with_missing_filename_policy(MissingFilenamePolicy::Error)
+            .add_text("k", "v")
+            .unwrap()
+            .add_stream(&mut &b"data"[..], "named", Some("a.bin"), None)
+            .unwrap()
+            .add_multipart("mixed", "mixed", MultipartBuilder::new())
+            .unwrap();
+        let err = builder
+            .add_stream(&mut &b"data"[..], "anon", None, None)
+            .unwrap_err();
+        let err = err.into_inner().unwrap().downcast::<MultipartError>();
+        assert_eq!(
+            *err.unwrap(),
+            MultipartError::MissingFilename {
+                name: "anon".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_header_folding() {
+        let builder = || {
+            MultipartBuilder::new()
+                .with_boundary("B")
+                .unwrap()
+                .add_bytes(
+                    "attachment",
+                    Some("a-rather-long-file-name.txt"),
+                    None,
+                    b"x",
+                )
+                .unwrap()
+        };
+        let (_, data) = builder().finish().unwrap();
+        let unfolded = String::from_utf8(data).unwrap();
+        assert!(unfolded.contains(
+            "\r\nContent-Disposition: form-data; name=\"attachment\"; filename=\"a-rather-long-file-name.txt\"\r\n"
+        ));
+
+        let (_, data) = builder().with_header_folding(Some(40)).finish().unwrap();
+        let folded = String::from_utf8(data).unwrap();
+        let expected = "--B\r\nContent-Disposition: form-data;\r\n name=\"attachment\";\r\n\
+            \x20filename=\"a-rather-long-file-name.txt\"\r\n\
+            Content-Type: application/octet-stream\r\n\r\nx\r\n--B--\r\n";
+        assert_eq!(folded, expected);
+        // unfolding, i.e. dropping each CRLF followed by a space, gives the
+        // original lines back
+        assert_eq!(folded.replace("\r\n ", " "), unfolded);
+        for line in folded.split("\r\n") {
+            assert!(line.len() <= 40 || !line.contains(' '), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn test_add_vec() {
+        let data = vec![7; 4096];
+        let ptr = data.as_ptr();
+        let builder = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_vec("v", Some("v.bin"), None, data.clone())
+            .unwrap();
+        let expected = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_bytes("v", Some("v.bin"), None, &data)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(builder.finish().unwrap(), expected);
+
+        let builder = MultipartBuilder::new()
+            .add_vec("v", None, None, data)
+            .unwrap();
+        match &builder.parts[0].body {
+            Body::Bytes(bytes) => assert_eq!(bytes.as_ptr(), ptr),
+            body => panic!("in memory part expected, got {body:?}"),
+        }
+    }
+
+    #[test]
+    fn test_has_files() {
+        let text = MultipartBuilder::new().add_text("k", "v").unwrap();
+        assert!(!text.has_files());
+        assert!(!MultipartBuilder::new().has_files());
+        let files = MultipartBuilder::new()
+            .add_file("f", "test-vector0.txt")
+            .unwrap();
+        assert!(files.has_files());
+        let mixed = text.add_bytes("b", None, None, b"").unwrap();
+        assert!(mixed.has_files());
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let builder = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .reserve_text("challenge")
+            .unwrap()
+            .add_text("k", "v")
+            .unwrap();
+        let err = builder.build().unwrap_err();
+        let err = err.into_inner().unwrap().downcast::<MultipartError>();
+        assert_eq!(
+            *err.unwrap(),
+            MultipartError::UnfilledPlaceholder {
+                name: "challenge".to_string()
+            }
+        );
+
+        let builder = builder.fill("challenge", "42").unwrap();
+        let (_, data) = builder.finish().unwrap();
+        let expected = "--B\r\nContent-Disposition: form-data; name=\"challenge\"\r\n\r\n42\r\n\
+            --B\r\nContent-Disposition: form-data; name=\"k\"\r\n\r\nv\r\n--B--\r\n";
+        assert_eq!(String::from_utf8(data).unwrap(), expected);
+
+        let builder = MultipartBuilder::new().reserve_text("a").unwrap();
+        let builder = builder.fill("a", "1").unwrap();
+        assert!(builder.fill("a", "2").is_err());
+    }
+
+    #[test]
+    fn test_duplicate_header_policy() {
+        let body = |policy| {
+            let builder = MultipartBuilder::new()
+                .with_boundary("B")
+                .unwrap()
+                .with_duplicate_header_policy(policy)
+                .add_text_with_headers(
+                    "k",
+                    "v",
+                    &[("X-Tag", "a"), ("X-Other", "o"), ("x-tag", "b")],
+                )
+                .unwrap();
+            builder
+                .finish()
+                .map(|(_, data)| String::from_utf8(data).unwrap())
+        };
+        let framed = |headers: &str| {
+            format!(
+                "--B\r\nContent-Disposition: form-data; name=\"k\"\r\n{headers}\r\n\r\nv\r\n--B--\r\n"
+            )
+        };
+        assert_eq!(
+            body(DuplicateHeaderPolicy::EmitAll).unwrap(),
+            framed("X-Tag: a\r\nX-Other: o\r\nx-tag: b")
+        );
+        assert_eq!(
+            body(DuplicateHeaderPolicy::Merge).unwrap(),
+            framed("X-Tag: a, b\r\nX-Other: o")
+        );
+        let err = body(DuplicateHeaderPolicy::Reject).unwrap_err();
+        let err = err.into_inner().unwrap().downcast::<MultipartError>();
+        assert_eq!(
+            *err.unwrap(),
+            MultipartError::DuplicateHeader {
+                name: "x-tag".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_to_curl() {
+        let builder = MultipartBuilder::new()
+            .add_text("name", "it's me")
+            .unwrap()
+            .add_text("plain", "v")
+            .unwrap()
+            .add_file("doc", "test-vector0.txt")
+            .unwrap()
+            .add_bytes("blob", Some("b.bin"), None, b"x")
+            .unwrap();
+        assert_eq!(
+            builder.to_curl("https://example.com/upload"),
+            "# in memory or streamed part \"blob\" left out\n\
+            curl --form-string 'name=it'\\''s me' --form-string plain=v \
+            -F 'doc=@test-vector0.txt;type=text/plain' https://example.com/upload"
+        );
+    }
+
+    #[test]
+    fn test_add_file_transformed() {
+        let original = fs::read("test-vector0.txt").unwrap();
+        let (_, data) = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_file_transformed("doc", "test-vector0.txt", |data| data.to_ascii_uppercase())
+            .unwrap()
+            .finish()
+            .unwrap();
+        let mut expected = b"--B\r\nContent-Disposition: form-data; name=\"doc\"; filename=\"test-vector0.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\n"
+            .to_vec();
+        expected.extend_from_slice(&original.to_ascii_uppercase());
+        expected.extend_from_slice(b"\r\n--B--\r\n");
+        assert_eq!(data, expected);
+        assert!(MultipartBuilder::new()
+            .add_file_transformed("doc", "missing.txt", |data| data)
+            .is_err());
+    }
+
+    #[test]
+    fn test_add_text_keyed() {
+        let (_, data) = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_text_keyed("c", "third", "3")
+            .unwrap()
+            .add_text("plain", "p")
+            .unwrap()
+            .add_text_keyed("a", "first", "1")
+            .unwrap()
+            .add_text_keyed("c", "fourth", "4")
+            .unwrap()
+            .add_text_keyed("b", "second", "2")
+            .unwrap()
+            .finish()
+            .unwrap();
+        let names: Vec<_> = String::from_utf8(data)
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.strip_prefix("Content-Disposition: form-data; name="))
+            .map(str::to_string)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "\"first\"",
+                "\"second\"",
+                "\"third\"",
+                "\"plain\"",
+                "\"fourth\""
+            ]
+        );
+    }
+
+    #[test]
+    fn test_add_read_limited() {
+        let (_, data) = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_read_limited("in", Some("in.txt"), None, &b"1234"[..], 4)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "--B\r\n\
+            Content-Disposition: form-data; name=\"in\"; filename=\"in.txt\"\r\n\
+            Content-Type: application/octet-stream\r\n\
+            \r\n\
+            1234\r\n\
+            --B--\r\n"
+        );
+
+        let err = MultipartBuilder::new()
+            .add_read_limited("in", None, None, io::repeat(b'x'), 4)
+            .unwrap_err();
+        let err = err
+            .into_inner()
+            .unwrap()
+            .downcast::<MultipartError>()
+            .unwrap();
+        assert_eq!(
+            *err,
+            MultipartError::PartTooLarge {
+                name: "in".to_string(),
+                max: 4
+            }
+        );
+    }
+
+    #[test]
+    fn test_contains_boundary() {
+        let builder = MultipartBuilder::new().with_boundary("BB").unwrap();
+        assert!(builder.contains_boundary(b"a\r\n--BB--\r\n"));
+        assert!(builder.contains_boundary(b"--BB"));
+        assert!(!builder.contains_boundary(b"BB"));
+        assert!(!builder.contains_boundary(b"--B-B"));
+        assert!(!builder.contains_boundary(b""));
+
+        let builder = MultipartBuilder::new();
+        let delimiter = builder.delimiter();
+        assert!(builder.contains_boundary(format!("x{delimiter}x").as_bytes()));
+        assert!(!builder.contains_boundary(&delimiter.as_bytes()[1..]));
+    }
+
+    #[test]
+    fn test_max_filename_len() {
+        let long = format!("{}.tar.gz", "x".repeat(300));
+        let builder = MultipartBuilder::new()
+            .add_bytes("before", Some(&long), None, b"")
+            .unwrap()
+            .with_max_filename_len(255)
+            .add_bytes("file", Some(&long), None, b"")
+            .unwrap()
+            .add_bytes("short", Some("a.txt"), None, b"")
+            .unwrap();
+        assert_eq!(builder.filename("before"), Some(long.as_str()));
+        let truncated = builder.filename("file").unwrap();
+        assert_eq!(truncated.len(), 255);
+        assert!(truncated.starts_with("xxx"));
+        assert!(truncated.ends_with("x.gz"));
+        assert_eq!(builder.filename("short"), Some("a.txt"));
+        assert_eq!(builder.filename("missing"), None);
+
+        let path =
+            std::env::temp_dir().join(format!("{}-{}.txt", "y".repeat(100), std::process::id()));
+        fs::write(&path, "a").unwrap();
+        let builder = MultipartBuilder::new()
+            .with_max_filename_len(20)
+            .add_file("file", &path)
+            .unwrap()
+            .add_stream(&mut &b"s"[..], "stream", Some(&long), None)
+            .unwrap();
+        assert_eq!(builder.filename("file"), Some("yyyyyyyyyyyyyyyy.txt"));
+        assert_eq!(builder.filename("stream"), Some("xxxxxxxxxxxxxxxxx.gz"));
+        let (_, data) = builder.finish().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(String::from_utf8(data)
+            .unwrap()
+            .contains("filename=\"yyyyyyyyyyyyyyyy.txt\""));
+
+        assert_eq!(truncate_filename("report.pdf", 8), "repo.pdf");
+        assert_eq!(truncate_filename("report.pdf", 10), "report.pdf");
+        // an extension as long as the limit isn't kept
+        assert_eq!(truncate_filename("a.verylongext", 5), "a.ver");
+        assert_eq!(truncate_filename(".bashrc_backup", 4), ".bas");
+        // 'é' is two bytes, it is dropped rather than split
+        assert_eq!(truncate_filename("ééé.txt", 8), "éé.txt");
+        assert_eq!(truncate_filename("ééé.txt", 7), "é.txt");
+    }
+
+    #[test]
+    fn test_wire_size_chunked() {
+        // 4\r\n....\r\n 4\r\n....\r\n 2\r\n..\r\n 0\r\n\r\n
+        assert_eq!(chunked_wire_size(10, 4), 9 + 9 + 7 + 5);
+        assert_eq!(chunked_wire_size(0, 4), 5);
+        assert_eq!(chunked_wire_size(16, 16), 22 + 5);
+        assert_eq!(chunked_wire_size(17, 16), 22 + 6 + 5);
+
+        let builder = MultipartBuilder::new()
+            .add_text("a", "1")
+            .unwrap()
+            .add_bytes("b", Some("b.bin"), None, &[7; 300])
+            .unwrap();
+        let (_, body) = builder.build().unwrap();
+        for chunk_size in [1, 15, 16, 100, body.len(), 4096] {
+            let mut framed = Vec::new();
+            for chunk in body.chunks(chunk_size) {
+                write!(framed, "{:x}\r\n", chunk.len()).unwrap();
+                framed.extend_from_slice(chunk);
+                framed.extend_from_slice(b"\r\n");
+            }
+            framed.extend_from_slice(b"0\r\n\r\n");
+            assert_eq!(
+                builder.wire_size_chunked(chunk_size).unwrap(),
+                Some(framed.len() as u64)
+            );
+        }
+        let err = builder.wire_size_chunked(0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let builder = builder.add_reader("c", None, None, io::empty()).unwrap();
+        assert_eq!(builder.wire_size_chunked(16).unwrap(), None);
+    }
+
+    #[test]
+    fn test_add_byte_iter() {
+        let mut next = b'a';
+        let alphabet = std::iter::from_fn(move || {
+            let byte = next;
+            next += 1;
+            (byte <= b'z').then_some(byte)
+        });
+        let (_, data) = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_byte_iter("f", Some("abc.txt"), Some(mime::TEXT_PLAIN), alphabet)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "--B\r\n\
+            Content-Disposition: form-data; name=\"f\"; filename=\"abc.txt\"\r\n\
+            Content-Type: text/plain\r\n\
+            \r\n\
+            abcdefghijklmnopqrstuvwxyz\r\n\
+            --B--\r\n"
+        );
+    }
+
+    #[test]
+    fn test_add_part() {
+        let build = |kinds: Vec<PartKind>| {
+            let mut builder = MultipartBuilder::new().with_boundary("B").unwrap();
+            for (idx, kind) in kinds.into_iter().enumerate() {
+                builder = builder.add_part(&format!("p{idx}"), kind).unwrap();
+            }
+            builder.finish().unwrap()
+        };
+        let unified = build(vec![
+            PartKind::Text("v".into()),
+            PartKind::File {
+                filename: Some("f.txt".into()),
+                content_type: Some(mime::TEXT_PLAIN),
+                data: b"data".to_vec(),
+            },
+            PartKind::File {
+                filename: None,
+                content_type: None,
+                data: b"raw".to_vec(),
+            },
+        ]);
+        let specific = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_text("p0", "v")
+            .unwrap()
+            .add_bytes("p1", Some("f.txt"), Some(mime::TEXT_PLAIN), b"data")
+            .unwrap()
+            .add_stream(&mut "raw".as_bytes(), "p2", None, None)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(unified, specific);
+        let data = String::from_utf8(unified.1).unwrap();
+        assert!(data.contains("name=\"p0\"\r\n\r\nv\r\n"));
+        assert!(
+            data.contains("name=\"p2\"\r\nContent-Type: application/octet-stream\r\n\r\nraw\r\n")
+        );
+    }
+
+    #[test]
+    fn test_content_type_params() {
+        let builder = MultipartBuilder::new();
+        let params = builder.content_type_params();
+        assert_eq!(params.keys().collect::<Vec<_>>(), ["boundary"]);
+        let boundary = &params["boundary"];
+        // 27 dashes in the parameter, the delimiter adds two more
+        assert_eq!(boundary.len(), 27 + 29);
+        assert!(boundary.starts_with(&"-".repeat(27)));
+        assert!(boundary[27..].bytes().all(|b| b.is_ascii_digit()));
+        assert_eq!(
+            builder.content_type(),
+            format!("multipart/form-data; boundary={boundary}")
+        );
+
+        let (content_type, data) = builder.add_text("k", "v").unwrap().finish().unwrap();
+        assert_eq!(
+            content_type,
+            format!("multipart/form-data; boundary={boundary}")
+        );
+        assert!(data.starts_with(format!("--{boundary}\r\n").as_bytes()));
+        assert!(data.ends_with(format!("\r\n--{boundary}--\r\n").as_bytes()));
+
+        let builder = MultipartBuilder::new().with_boundary("a'b(c) d").unwrap();
+        assert_eq!(
+            builder.content_type(),
+            "multipart/form-data; boundary=\"a'b(c) d\""
+        );
+        assert_eq!(builder.content_type_params()["boundary"], "a'b(c) d");
+    }
+
+    #[test]
+    fn test_content_type_param_order() {
+        let (_, data) = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_bytes_with_params(
+                "doc",
+                None,
+                mime::TEXT_PLAIN,
+                &[
+                    ("charset", "utf-8"),
+                    ("format", "flowed"),
+                    ("x-note", "a b"),
+                ],
+                b"hi",
+            )
+            .unwrap()
+            .finish()
+            .unwrap();
+        let expected = "--B\r\nContent-Disposition: form-data; name=\"doc\"\r\n\
+            Content-Type: text/plain; charset=utf-8; format=flowed; x-note=\"a b\"\r\n\r\n\
+            hi\r\n--B--\r\n";
+        assert_eq!(String::from_utf8(data).unwrap(), expected);
+
+        let err = MultipartBuilder::new()
+            .add_bytes_with_params("doc", None, mime::TEXT_PLAIN, &[("", "x")], b"")
+            .unwrap_err();
+        let err = err.into_inner().unwrap().downcast::<MultipartError>();
+        assert!(matches!(
+            *err.unwrap(),
+            MultipartError::InvalidHeader { .. }
+        ));
+    }
+
     #[test]
     fn test_ureq() {
         let p0 = Path::new("test-vector0.txt");
@@ -0,0 +1,132 @@
+use crate::{Body, MultipartBuilder, Part};
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::io;
+
+/// layout of a checksum manifest, see [`MultipartBuilder::add_manifest`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    /// `sha256sum` style text, one `<hex digest>  <filename>` line per file
+    Sha256Sum,
+    /// a JSON array of `{"filename": .., "sha256": ..}` objects
+    Json,
+}
+
+impl MultipartBuilder {
+    /// add a part listing the sha-256 digest of every file added so far
+    ///
+    /// * name manifest field name
+    /// * format manifest layout
+    ///
+    /// Files are listed in the order they were added, under their filename,
+    /// or their field name when they have none. Files are read to be hashed,
+    /// streams that can only be read once can't be listed.
+    pub fn add_manifest(self, name: &str, format: ManifestFormat) -> io::Result<Self> {
+        let digests = self
+            .parts
+            .iter()
+            .filter(|part| part.is_file())
+            .map(|part| {
+                let filename = part.filename.as_deref().unwrap_or(&part.name);
+                Ok((filename, hex(&sha256(part)?)))
+            })
+            .collect::<io::Result<Vec<_>>>()?
+            .into_iter();
+        let (manifest, content_type) = match format {
+            ManifestFormat::Sha256Sum => {
+                let mut manifest = String::new();
+                for (filename, digest) in digests {
+                    let _ = writeln!(manifest, "{digest}  {filename}");
+                }
+                (manifest, mime::TEXT_PLAIN_UTF_8)
+            }
+            ManifestFormat::Json => {
+                let entries: Vec<_> = digests
+                    .map(|(filename, digest)| {
+                        format!(
+                            "{{\"filename\":{},\"sha256\":\"{digest}\"}}",
+                            json_string(filename)
+                        )
+                    })
+                    .collect();
+                (format!("[{}]", entries.join(",")), mime::APPLICATION_JSON)
+            }
+        };
+        let body = Body::Bytes(manifest.into_bytes());
+        self.push_part(name, None, Some(content_type), &[], body)
+    }
+}
+
+pub(crate) fn sha256(part: &Part) -> io::Result<Vec<u8>> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut part.data_reader()?, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DIGEST0: &str = "be80f7ca3a89c520c53214df1c0735d49056427d98471c857f4695c405a97f7f";
+    const DIGEST1: &str = "6f09eada19649d81c3921696ac3adb00cdace83221149e12849896073005f5c3";
+
+    fn manifest(format: ManifestFormat) -> String {
+        let (_, data) = MultipartBuilder::new()
+            .add_file("a", "test-vector0.txt")
+            .unwrap()
+            .add_text("note", "not listed")
+            .unwrap()
+            .add_file("b", "test-vector1.txt")
+            .unwrap()
+            .add_stream(&mut "x".as_bytes(), "c\"d", None, None)
+            .unwrap()
+            .add_manifest("manifest", format)
+            .unwrap()
+            .finish()
+            .unwrap();
+        let data = String::from_utf8(data).unwrap();
+        let start = data.rfind("\r\n\r\n").unwrap() + 4;
+        let end = data[start..].find("\r\n--").unwrap();
+        data[start..start + end].to_string()
+    }
+
+    #[test]
+    fn test_manifest() {
+        assert_eq!(
+            manifest(ManifestFormat::Sha256Sum),
+            format!(
+                "{DIGEST0}  test-vector0.txt\n\
+                {DIGEST1}  test-vector1.txt\n\
+                2d711642b726b04401627ca9fbac32f5c8530fb1903cc4db02258717921a4881  c\"d\n"
+            )
+        );
+        assert_eq!(
+            manifest(ManifestFormat::Json),
+            format!(
+                "[{{\"filename\":\"test-vector0.txt\",\"sha256\":\"{DIGEST0}\"}},\
+                {{\"filename\":\"test-vector1.txt\",\"sha256\":\"{DIGEST1}\"}},\
+                {{\"filename\":\"c\\\"d\",\"sha256\":\"2d711642b726b04401627ca9fbac32f5c8530fb1903cc4db02258717921a4881\"}}]"
+            )
+        );
+    }
+}
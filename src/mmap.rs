@@ -0,0 +1,134 @@
+use crate::{mime_filename, Body, MultipartBuilder, MultipartError};
+use memmap2::{Mmap, MmapOptions};
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+/// mapping offsets are rounded down to this, a multiple of the page size
+/// and of the Windows allocation granularity
+const MAP_ALIGN: u64 = 64 << 10;
+
+/// the requested bytes of a mapping starting at an aligned offset
+#[derive(Clone)]
+pub(crate) struct MappedRange {
+    map: Arc<Mmap>,
+    skip: usize,
+}
+
+impl AsRef<[u8]> for MappedRange {
+    fn as_ref(&self) -> &[u8] {
+        &self.map[self.skip..]
+    }
+}
+
+impl MultipartBuilder {
+    /// add a range of a file, memory mapped instead of read
+    ///
+    /// * name file field name
+    /// * path the file, the filename and content type are its
+    /// * range the bytes to send, only these are mapped
+    ///
+    /// e.g. for chunked uploads of huge files. The range must be within the
+    /// file and not empty.
+    ///
+    /// # Safety
+    ///
+    /// The mapped bytes must not change until the body is produced, e.g. by
+    /// another process writing to or truncating the file.
+    pub unsafe fn add_mmap_range<P: AsRef<Path>>(
+        self,
+        name: &str,
+        path: P,
+        range: Range<u64>,
+    ) -> io::Result<Self> {
+        if range.is_empty() {
+            return Err(MultipartError::EmptyRange {
+                start: range.start,
+                end: range.end,
+            }
+            .into());
+        }
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        if range.end > len {
+            return Err(MultipartError::RangeOutOfBounds {
+                start: range.start,
+                end: range.end,
+                len,
+            }
+            .into());
+        }
+        let offset = range.start - range.start % MAP_ALIGN;
+        let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "range too large to map");
+        let map_len = usize::try_from(range.end - offset).map_err(|_| too_large())?;
+        let skip = (range.start - offset) as usize;
+        // SAFETY: the caller keeps the file unchanged, the range is within it
+        let map = unsafe { MmapOptions::new().offset(offset).len(map_len).map(&file)? };
+        let body = Body::Mmap(MappedRange {
+            map: Arc::new(map),
+            skip,
+        });
+        let (content_type, filename) = mime_filename(path);
+        self.push_part(name, filename, Some(content_type), &[], body)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::contains;
+    use std::fs;
+
+    #[test]
+    fn test_add_mmap_range() {
+        let path =
+            std::env::temp_dir().join(format!("ureq-multipart-mmap-{}.bin", std::process::id()));
+        let data: Vec<u8> = (0..3 * MAP_ALIGN).map(|idx| (idx % 251) as u8).collect();
+        fs::write(&path, &data).unwrap();
+
+        // across an alignment boundary, not starting on one
+        let range = MAP_ALIGN - 10..MAP_ALIGN + 20;
+        let builder =
+            unsafe { MultipartBuilder::new().add_mmap_range("chunk", &path, range.clone()) }
+                .unwrap()
+                .add_text("after", "x")
+                .unwrap();
+        assert_eq!(
+            builder.parts[0].filename.as_deref(),
+            path.file_name().unwrap().to_str()
+        );
+        let expected = &data[range.start as usize..range.end as usize];
+        assert_eq!(builder.parts[0].body.known_len().unwrap(), Some(30));
+        let (_, first) = builder.build().unwrap();
+        let (_, second) = builder.finish().unwrap();
+        assert_eq!(first, second);
+        assert!(contains(
+            &first,
+            &[b"\r\n\r\n", expected, b"\r\n--"].concat()
+        ));
+
+        let err =
+            unsafe { MultipartBuilder::new().add_mmap_range("chunk", &path, 5..5) }.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let len = data.len() as u64;
+        let err = unsafe { MultipartBuilder::new().add_mmap_range("chunk", &path, 0..len + 1) }
+            .unwrap_err();
+        let err = err
+            .into_inner()
+            .unwrap()
+            .downcast::<MultipartError>()
+            .unwrap();
+        assert_eq!(
+            *err,
+            MultipartError::RangeOutOfBounds {
+                start: 0,
+                end: len + 1,
+                len
+            }
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
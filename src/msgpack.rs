@@ -0,0 +1,45 @@
+use crate::MultipartBuilder;
+use serde::Serialize;
+use std::io;
+
+impl MultipartBuilder {
+    /// add a value serialized as MessagePack
+    ///
+    /// * name field name
+    /// * value the value, serialized right away
+    ///
+    /// The part has no filename and the `application/msgpack` content type.
+    /// Structs are serialized as maps, with their field names.
+    pub fn add_msgpack<T: Serialize + ?Sized>(self, name: &str, value: &T) -> io::Result<Self> {
+        let data = rmp_serde::to_vec_named(value)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let content_type = "application/msgpack".parse().expect("valid mime");
+        self.add_bytes(name, None, Some(content_type), &data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Body;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_msgpack() {
+        let value = BTreeMap::from([
+            ("id".to_string(), vec![1u32, 2]),
+            ("tags".to_string(), vec![]),
+        ]);
+        let builder = MultipartBuilder::new().add_msgpack("meta", &value).unwrap();
+        let part = &builder.parts[0];
+        assert_eq!(
+            part.content_type.as_ref().unwrap().essence_str(),
+            "application/msgpack"
+        );
+        let Body::Bytes(data) = &part.body else {
+            panic!("in memory part expected");
+        };
+        let decoded: BTreeMap<String, Vec<u32>> = rmp_serde::from_slice(data).unwrap();
+        assert_eq!(decoded, value);
+    }
+}
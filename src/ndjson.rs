@@ -0,0 +1,60 @@
+use crate::MultipartBuilder;
+use serde::Serialize;
+use std::io;
+
+impl MultipartBuilder {
+    /// add values as newline delimited JSON, e.g. for bulk ingestion
+    ///
+    /// * name field name
+    /// * items the values, one per line, serialized right away
+    ///
+    /// Lines are joined with `\n`, there is none after the last one. The part
+    /// has no filename and the `application/x-ndjson` content type.
+    pub fn add_ndjson<T: Serialize>(self, name: &str, items: &[T]) -> io::Result<Self> {
+        let mut data = Vec::new();
+        for (idx, item) in items.iter().enumerate() {
+            if idx > 0 {
+                data.push(b'\n');
+            }
+            serde_json::to_writer(&mut data, item)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        }
+        let content_type = "application/x-ndjson".parse().expect("valid mime");
+        self.add_bytes(name, None, Some(content_type), &data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Body;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_ndjson() {
+        let items = vec![
+            BTreeMap::from([("index".to_string(), "logs".to_string())]),
+            BTreeMap::from([("message".to_string(), "a\nb".to_string())]),
+        ];
+        let builder = MultipartBuilder::new().add_ndjson("bulk", &items).unwrap();
+        let part = &builder.parts[0];
+        assert_eq!(
+            part.content_type.as_ref().unwrap().essence_str(),
+            "application/x-ndjson"
+        );
+        let Body::Bytes(data) = &part.body else {
+            panic!("in memory part expected");
+        };
+        let lines: Vec<BTreeMap<String, String>> = std::str::from_utf8(data)
+            .unwrap()
+            .split('\n')
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines, items);
+
+        let builder = MultipartBuilder::new()
+            .add_ndjson::<u8>("bulk", &[])
+            .unwrap();
+        assert!(matches!(&builder.parts[0].body, Body::Bytes(data) if data.is_empty()));
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{Body, MultipartBuilder, MultipartError};
+use mime::Mime;
+use std::io;
+
+impl MultipartBuilder {
+    /// how deep nested multipart bodies may go, 4 by default
+    ///
+    /// A body without nested multipart parts has a depth of 0, adding it to
+    /// another one with [`add_multipart`](Self::add_multipart) gives a depth
+    /// of 1, and so on.
+    pub fn with_max_nesting_depth(mut self, depth: usize) -> Self {
+        self.max_nesting_depth = depth;
+        self
+    }
+    /// add a nested multipart body
+    ///
+    /// * name field name
+    /// * subtype multipart subtype of the part, e.g. `mixed`
+    /// * builder the nested body, with its own boundary
+    ///
+    /// Fails when the nesting gets deeper than
+    /// [allowed](Self::with_max_nesting_depth).
+    pub fn add_multipart(
+        self,
+        name: &str,
+        subtype: &str,
+        builder: MultipartBuilder,
+    ) -> io::Result<Self> {
+        let depth = builder.nesting_depth() + 1;
+        if depth > self.max_nesting_depth {
+            return Err(MultipartError::NestingTooDeep {
+                depth,
+                max: self.max_nesting_depth,
+            }
+            .into());
+        }
+        let content_type = format!("multipart/{subtype}; boundary={}", builder.boundary_param());
+        let content_type = content_type.parse::<Mime>().map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{content_type:?}: {err}"),
+            )
+        })?;
+        let body = Body::Nested(Box::new(builder));
+        self.push_part(name, None, Some(content_type), &[], body)
+    }
+    /// add a nested multipart body built by a closure
+    ///
+    /// * name field name
+    /// * subtype multipart subtype of the part, e.g. `mixed`
+    /// * build gets a new builder and returns the nested body
+    ///
+    /// Like [`add_multipart`](Self::add_multipart), but the nested builder
+    /// is created here. Its boundary is redrawn when it overlaps this one,
+    /// unless the closure fixed it.
+    pub fn add_nested<F>(self, name: &str, subtype: &str, build: F) -> io::Result<Self>
+    where
+        F: FnOnce(MultipartBuilder) -> io::Result<MultipartBuilder>,
+    {
+        let mut builder = build(MultipartBuilder::new())?;
+        let outer = self.delimiter();
+        let mut attempts = 0;
+        while !builder.boundary_fixed && attempts < self.max_boundary_attempts {
+            let inner = builder.delimiter();
+            if !inner.contains(&outer) && !outer.contains(&inner) {
+                break;
+            }
+            builder.boundary = builder.next_boundary(builder.boundary.len())?;
+            builder.avoid_collisions()?;
+            attempts += 1;
+        }
+        self.add_multipart(name, subtype, builder)
+    }
+    /// how many multipart bodies are nested in this one, at most
+    pub fn nesting_depth(&self) -> usize {
+        self.parts
+            .iter()
+            .filter_map(|part| match &part.body {
+                Body::Nested(builder) => Some(builder.nesting_depth() + 1),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn nested(depth: usize) -> io::Result<MultipartBuilder> {
+        let mut builder = MultipartBuilder::new().add_text("leaf", "v")?;
+        for _ in 0..depth {
+            builder = MultipartBuilder::new()
+                .with_max_nesting_depth(usize::MAX)
+                .add_multipart("inner", "mixed", builder)?;
+        }
+        Ok(builder)
+    }
+
+    #[test]
+    fn test_nested() {
+        let inner = MultipartBuilder::new()
+            .with_boundary("inner")
+            .unwrap()
+            .add_text("k", "v")
+            .unwrap();
+        let (_, data) = MultipartBuilder::new()
+            .with_boundary("outer")
+            .unwrap()
+            .add_multipart("batch", "mixed", inner)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "--outer\r\n\
+            Content-Disposition: form-data; name=\"batch\"\r\n\
+            Content-Type: multipart/mixed; boundary=inner\r\n\
+            \r\n\
+            --inner\r\n\
+            Content-Disposition: form-data; name=\"k\"\r\n\
+            \r\n\
+            v\r\n\
+            --inner--\r\n\
+            \r\n\
+            --outer--\r\n"
+        );
+    }
+
+    #[test]
+    fn test_nesting_depth_limit() {
+        assert_eq!(nested(3).unwrap().nesting_depth(), 3);
+        let builder = MultipartBuilder::new()
+            .add_multipart("batch", "mixed", nested(3).unwrap())
+            .unwrap();
+        assert_eq!(builder.nesting_depth(), 4);
+        assert!(builder.build().is_ok());
+
+        let err = MultipartBuilder::new()
+            .add_multipart("batch", "mixed", nested(4).unwrap())
+            .unwrap_err();
+        let err = err
+            .into_inner()
+            .unwrap()
+            .downcast::<MultipartError>()
+            .unwrap();
+        assert_eq!(*err, MultipartError::NestingTooDeep { depth: 5, max: 4 });
+
+        let err = MultipartBuilder::new()
+            .with_max_nesting_depth(1)
+            .add_multipart("batch", "mixed", nested(1).unwrap())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_nested_boundary_collision() {
+        // the inner delimiter starts with the outer one
+        let inner = MultipartBuilder::new().with_boundary("B2").unwrap();
+        let err = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_multipart("batch", "mixed", inner)
+            .unwrap_err();
+        let err = err
+            .into_inner()
+            .unwrap()
+            .downcast::<MultipartError>()
+            .unwrap();
+        assert!(matches!(*err, MultipartError::BoundaryCollision { .. }));
+    }
+
+    #[test]
+    fn test_add_nested() {
+        let (content_type, data) = MultipartBuilder::new()
+            .add_text("top", "1")
+            .unwrap()
+            .add_nested("batch", "mixed", |builder| {
+                builder
+                    .add_text("a", "2")?
+                    .add_nested("deeper", "alternative", |builder| {
+                        builder.add_text("b", "3")
+                    })
+            })
+            .unwrap()
+            .finish()
+            .unwrap();
+        let data = String::from_utf8(data).unwrap();
+        let boundary = |content_type: &str| {
+            content_type
+                .split_once("boundary=")
+                .unwrap()
+                .1
+                .trim_matches('"')
+                .to_string()
+        };
+        let outer = boundary(&content_type);
+        let types: Vec<_> = data
+            .lines()
+            .filter_map(|line| line.strip_prefix("Content-Type: multipart/"))
+            .collect();
+        assert_eq!(types.len(), 2);
+        let mixed = boundary(types[0]);
+        let alternative = boundary(types[1]);
+        assert!(types[0].starts_with("mixed;"));
+        assert!(types[1].starts_with("alternative;"));
+        assert_ne!(outer, mixed);
+        assert_ne!(mixed, alternative);
+        for boundary in [&outer, &mixed, &alternative] {
+            assert_eq!(data.matches(&format!("--{boundary}--\r\n")).count(), 1);
+        }
+
+        // a nested body clashing with the outer boundary gets a new one
+        let builder = MultipartBuilder::new()
+            .with_boundary_prefix("")
+            .unwrap()
+            .with_boundary_len(1)
+            .unwrap();
+        let outer = builder.delimiter();
+        let builder = builder
+            .add_nested("n", "mixed", |builder| {
+                builder.with_boundary_prefix("")?.with_boundary_len(1)
+            })
+            .unwrap();
+        assert_eq!(builder.delimiter(), outer);
+        let Body::Nested(inner) = &builder.parts[0].body else {
+            panic!("nested part expected");
+        };
+        assert_ne!(inner.delimiter(), outer);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::MultipartBuilder;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// the filesystem calls made for added files, which may be retried
+pub(crate) trait FileCheck: Send + Sync {
+    /// whether `path` is a symlink, without following it
+    fn is_symlink(&self, path: &Path) -> io::Result<bool>;
+    /// open `path` for reading
+    fn open(&self, path: &Path) -> io::Result<File>;
+}
+
+/// the actual filesystem
+pub(crate) struct RealFs;
+
+impl FileCheck for RealFs {
+    fn is_symlink(&self, path: &Path) -> io::Result<bool> {
+        Ok(fs::symlink_metadata(path)?.is_symlink())
+    }
+    fn open(&self, path: &Path) -> io::Result<File> {
+        File::open(path)
+    }
+}
+
+/// the builder's filesystem, which can't be debugged
+pub(crate) struct FileCheckBox(pub(crate) Arc<dyn FileCheck>);
+
+impl fmt::Debug for FileCheckBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FileCheck")
+    }
+}
+
+/// opens files with the builder's retries, carried into the body stream
+#[derive(Clone)]
+pub(crate) struct FileOpener {
+    attempts: usize,
+    backoff: Duration,
+    fs: Arc<dyn FileCheck>,
+}
+
+impl Default for FileOpener {
+    fn default() -> Self {
+        Self {
+            attempts: 1,
+            backoff: Duration::ZERO,
+            fs: Arc::new(RealFs),
+        }
+    }
+}
+
+impl FileOpener {
+    /// open a file for reading, retrying transient failures
+    pub(crate) fn open(&self, path: &Path) -> io::Result<File> {
+        self.retry(path, |path| self.fs.open(path))
+    }
+    fn retry<T, F>(&self, path: &Path, mut open: F) -> io::Result<T>
+    where
+        F: FnMut(&Path) -> io::Result<T>,
+    {
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+        loop {
+            match open(path) {
+                Err(err) if attempt < self.attempts && is_transient(&err) => {
+                    thread::sleep(backoff);
+                    backoff = backoff.saturating_mul(2);
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+impl MultipartBuilder {
+    /// try opening files up to `attempts` times, e.g. on network filesystems
+    ///
+    /// Once by default. Looking up and opening a file when it's added, and
+    /// opening it again when the body is produced, are each retried on
+    /// transient failures: the first retry waits `backoff`, doubled on every
+    /// retry after it, e.g. with 3 attempts and 100ms tries happen after 0,
+    /// 100 and 300ms.
+    ///
+    /// Only errors that may go away are retried: interrupted calls, would
+    /// block, timeouts, busy resources and stale network file handles (`EBUSY`
+    /// and `ESTALE`). Anything else, e.g. a missing file or denied access,
+    /// fails right away.
+    pub fn with_open_retries(mut self, attempts: usize, backoff: Duration) -> Self {
+        self.open_attempts = attempts.max(1);
+        self.open_backoff = backoff;
+        self
+    }
+    /// how the builder's files are opened
+    pub(crate) fn file_opener(&self) -> FileOpener {
+        FileOpener {
+            attempts: self.open_attempts,
+            backoff: self.open_backoff,
+            fs: self.file_check.0.clone(),
+        }
+    }
+    /// whether an added file is a symlink, retrying transient failures
+    pub(crate) fn check_symlink(&self, path: &Path) -> io::Result<bool> {
+        self.retry_open(path, |path| self.file_check.0.is_symlink(path))
+    }
+    /// open a file to report errors early, retrying transient failures
+    pub(crate) fn check_open(&self, path: &Path) -> io::Result<()> {
+        self.retry_open(path, |path| self.file_check.0.open(path).map(drop))
+    }
+    fn retry_open<T, F>(&self, path: &Path, open: F) -> io::Result<T>
+    where
+        F: FnMut(&Path) -> io::Result<T>,
+    {
+        self.file_opener().retry(path, open)
+    }
+}
+
+/// errors that may go away on their own, the only ones retried
+fn is_transient(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ResourceBusy
+            | io::ErrorKind::StaleNetworkFileHandle
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// fails `failures` times, like a flaky mount would
+    fn flaky(
+        failures: usize,
+        kind: io::ErrorKind,
+    ) -> (impl FnMut(&Path) -> io::Result<()>, Rc<Cell<usize>>) {
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        let open = move |_: &Path| {
+            counter.set(counter.get() + 1);
+            if counter.get() <= failures {
+                Err(io::Error::new(kind, "flaky"))
+            } else {
+                Ok(())
+            }
+        };
+        (open, calls)
+    }
+
+    #[test]
+    fn test_open_retries() {
+        let path = Path::new("remote/file.bin");
+        let builder = MultipartBuilder::new();
+        let (open, calls) = flaky(1, io::ErrorKind::TimedOut);
+        assert!(builder.retry_open(path, open).is_err());
+        assert_eq!(calls.get(), 1);
+
+        let builder = builder.with_open_retries(3, Duration::from_millis(1));
+        let (open, calls) = flaky(2, io::ErrorKind::TimedOut);
+        builder.retry_open(path, open).unwrap();
+        assert_eq!(calls.get(), 3);
+
+        let (open, calls) = flaky(3, io::ErrorKind::TimedOut);
+        let err = builder.retry_open(path, open).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(calls.get(), 3);
+
+        let (open, calls) = flaky(2, io::ErrorKind::ResourceBusy);
+        builder.retry_open(path, open).unwrap();
+        assert_eq!(calls.get(), 3);
+
+        for kind in [io::ErrorKind::NotFound, io::ErrorKind::InvalidInput] {
+            let (open, calls) = flaky(1, kind);
+            assert!(builder.retry_open(path, open).is_err());
+            assert_eq!(calls.get(), 1);
+        }
+
+        let err = builder.add_file("f", "does/not/exist").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    /// the real filesystem, failing its first `failures` calls
+    struct FlakyFs {
+        failures: usize,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl FlakyFs {
+        fn fail(&self) -> io::Result<()> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "flaky"));
+            }
+            Ok(())
+        }
+    }
+
+    impl FileCheck for FlakyFs {
+        fn is_symlink(&self, path: &Path) -> io::Result<bool> {
+            self.fail()?;
+            RealFs.is_symlink(path)
+        }
+        fn open(&self, path: &Path) -> io::Result<File> {
+            self.fail()?;
+            RealFs.open(path)
+        }
+    }
+
+    fn flaky_builder(failures: usize, attempts: usize) -> (MultipartBuilder, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut builder =
+            MultipartBuilder::new().with_open_retries(attempts, Duration::from_millis(1));
+        builder.file_check = FileCheckBox(Arc::new(FlakyFs {
+            failures,
+            calls: calls.clone(),
+        }));
+        (builder, calls)
+    }
+
+    #[test]
+    fn test_add_file_open_retries() {
+        let path =
+            std::env::temp_dir().join(format!("ureq-multipart-retry-{}.txt", std::process::id()));
+        fs::write(&path, "a").unwrap();
+
+        // the lookup fails twice, then the open goes through
+        let (builder, calls) = flaky_builder(2, 3);
+        let builder = builder.add_file("file", &path).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        assert_eq!(builder.parts.len(), 1);
+
+        let (builder, calls) = flaky_builder(3, 3);
+        let err = builder.add_file("file", &path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // a single attempt by default
+        let (builder, calls) = flaky_builder(1, 1);
+        assert!(builder.add_file("file", &path).is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_body_open_retries() {
+        let path =
+            std::env::temp_dir().join(format!("ureq-multipart-body-{}.txt", std::process::id()));
+        fs::write(&path, "a").unwrap();
+
+        // the file is opened again when the body is produced, failing twice
+        let (builder, calls) = flaky_builder(2, 3);
+        let builder = builder.add_file("file", &path).unwrap();
+        calls.store(0, Ordering::SeqCst);
+        let (_, data) = builder.finish().unwrap();
+        assert!(String::from_utf8(data).unwrap().contains("\r\n\r\na\r\n"));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let (builder, calls) = flaky_builder(2, 3);
+        let builder = builder.add_file("file", &path).unwrap();
+        calls.store(0, Ordering::SeqCst);
+        let builder = builder.with_open_retries(2, Duration::from_millis(1));
+        let (_, mut reader) = builder.into_reader().unwrap();
+        let err = io::copy(&mut reader, &mut io::sink()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        fs::remove_file(&path).unwrap();
+    }
+}
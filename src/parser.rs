@@ -0,0 +1,314 @@
+use crate::{Body, MultipartBuilder, MultipartError};
+use mime::Mime;
+
+/// a part of a body parsed by [`parse_multipart`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedPart {
+    /// the `name` disposition parameter, as written
+    pub name: String,
+    /// the `filename` disposition parameter, as written
+    pub filename: Option<String>,
+    /// the `Content-Type` header value
+    pub content_type: Option<String>,
+    /// every header of the part, folded lines unfolded
+    pub headers: Vec<(String, String)>,
+    pub data: Vec<u8>,
+}
+
+fn malformed(reason: impl Into<String>) -> MultipartError {
+    MultipartError::MalformedBody {
+        reason: reason.into(),
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// parse a `multipart/form-data` body, strictly
+///
+/// * content_type the content type the body is sent with, holding the boundary
+/// * body the whole body
+///
+/// The body must start with a delimiter, delimiter lines must end with a
+/// CRLF, and every part needs a `form-data` disposition with a `name`.
+/// Quoted parameter values end at the next quote, there are no escapes, so
+/// an unescaped quote in a name fails. Folded headers are unfolded, an empty
+/// part may end right after its headers, and the epilogue is ignored.
+pub fn parse_multipart(content_type: &str, body: &[u8]) -> Result<Vec<ParsedPart>, MultipartError> {
+    let content_type: Mime = content_type
+        .parse()
+        .map_err(|_| malformed("invalid content type"))?;
+    if content_type.type_() != mime::MULTIPART {
+        return Err(malformed("not a multipart content type"));
+    }
+    let boundary = content_type
+        .get_param(mime::BOUNDARY)
+        .ok_or_else(|| malformed("no boundary"))?;
+    let delimiter = format!("--{boundary}");
+    let next_delimiter = format!("\r\n{delimiter}");
+    let mut rest = body
+        .strip_prefix(delimiter.as_bytes())
+        .ok_or_else(|| malformed("missing first delimiter"))?;
+    let mut parts = Vec::new();
+    while !rest.starts_with(b"--") {
+        rest = rest
+            .strip_prefix(b"\r\n")
+            .ok_or_else(|| malformed("delimiter not followed by CRLF"))?;
+        let mut headers: Vec<(String, String)> = Vec::new();
+        loop {
+            let end = find(rest, b"\r\n").ok_or_else(|| malformed("unterminated headers"))?;
+            let line =
+                std::str::from_utf8(&rest[..end]).map_err(|_| malformed("header is not UTF-8"))?;
+            rest = &rest[end + 2..];
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with([' ', '\t']) {
+                let (_, value) = headers
+                    .last_mut()
+                    .ok_or_else(|| malformed("continuation line without header"))?;
+                value.push_str(line);
+                continue;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| malformed(format!("header without colon {line:?}")))?;
+            if name.is_empty() || !name.bytes().all(|b| b.is_ascii_graphic()) {
+                return Err(malformed(format!("invalid header name {name:?}")));
+            }
+            headers.push((name.to_string(), value.to_string()));
+        }
+        for (_, value) in &mut headers {
+            *value = value.trim().to_string();
+        }
+        // an empty part may end right after its headers, the CRLF before the
+        // delimiter was then read as the blank line
+        let (data, after) = match rest.strip_prefix(delimiter.as_bytes()) {
+            Some(after) => (&rest[..0], after),
+            None => {
+                let end = find(rest, next_delimiter.as_bytes())
+                    .ok_or_else(|| malformed("missing closing delimiter"))?;
+                (&rest[..end], &rest[end + next_delimiter.len()..])
+            }
+        };
+        parts.push(parsed_part(headers, data.to_vec())?);
+        rest = after;
+    }
+    Ok(parts)
+}
+
+fn parsed_part(
+    headers: Vec<(String, String)>,
+    data: Vec<u8>,
+) -> Result<ParsedPart, MultipartError> {
+    let header = |name: &str| {
+        let mut values = headers
+            .iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.clone());
+        match (values.next(), values.next()) {
+            (_, Some(_)) => Err(malformed(format!("more than one {name} header"))),
+            (value, None) => Ok(value),
+        }
+    };
+    let disposition = header("Content-Disposition")?
+        .ok_or_else(|| malformed("part without Content-Disposition"))?;
+    let content_type = header("Content-Type")?;
+    let mut name = None;
+    let mut filename = None;
+    for (param, value) in disposition_params(&disposition)? {
+        let slot = match param.as_str() {
+            "name" => &mut name,
+            "filename" => &mut filename,
+            _ => continue,
+        };
+        if slot.replace(value).is_some() {
+            return Err(malformed(format!("duplicate {param} parameter")));
+        }
+    }
+    Ok(ParsedPart {
+        name: name.ok_or_else(|| malformed("part without name"))?,
+        filename,
+        content_type,
+        headers,
+        data,
+    })
+}
+
+/// the parameters of a `form-data` disposition, names lowercased
+fn disposition_params(disposition: &str) -> Result<Vec<(String, String)>, MultipartError> {
+    let (kind, mut rest) = disposition.split_once(';').unwrap_or((disposition, ""));
+    if !kind.trim().eq_ignore_ascii_case("form-data") {
+        return Err(malformed(format!("disposition {kind:?} is not form-data")));
+    }
+    let mut params = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return Ok(params);
+        }
+        let (name, after) = rest
+            .split_once('=')
+            .ok_or_else(|| malformed(format!("parameter without value {rest:?}")))?;
+        let name = name.trim().to_ascii_lowercase();
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted
+                    .find('"')
+                    .ok_or_else(|| malformed(format!("unterminated {name} parameter")))?;
+                (quoted[..end].to_string(), &quoted[end + 1..])
+            }
+            None => {
+                let end = after.find(';').unwrap_or(after.len());
+                (after[..end].trim().to_string(), &after[end..])
+            }
+        };
+        let after = after.trim_start();
+        rest = match after.strip_prefix(';') {
+            Some(after) => after,
+            None if after.is_empty() => after,
+            None => {
+                return Err(malformed(format!(
+                    "unexpected {after:?} after {name} parameter"
+                )))
+            }
+        };
+        params.push((name, value));
+    }
+}
+
+impl MultipartBuilder {
+    /// check that the body parses with [`parse_multipart`] and round-trips
+    ///
+    /// The body is produced like with [`build`](Self::build), so it fails for
+    /// streams that can only be read once. Every part must come back, with
+    /// its name and filename, and in memory data must be unchanged unless it
+    /// has a transfer encoding.
+    pub fn self_verify(&self) -> Result<(), MultipartError> {
+        let (content_type, body) = self.build().map_err(|err| {
+            match err
+                .get_ref()
+                .and_then(|err| err.downcast_ref::<MultipartError>())
+            {
+                Some(err) => err.clone(),
+                None => malformed(format!("body can't be produced: {err}")),
+            }
+        })?;
+        let parsed = parse_multipart(&content_type, &body)?;
+        if parsed.len() != self.parts.len() {
+            return Err(malformed(format!(
+                "{} parts parsed out of {}",
+                parsed.len(),
+                self.parts.len()
+            )));
+        }
+        for (part, parsed) in self.parts.iter().zip(parsed) {
+            let filename = part
+                .filename
+                .as_deref()
+                .map(|filename| self.disposition_value(filename));
+            if parsed.name != self.disposition_value(&part.name)
+                || parsed.filename.as_deref() != filename.as_deref()
+            {
+                return Err(malformed(format!(
+                    "part {:?} parsed as {:?}",
+                    part.name, parsed.name
+                )));
+            }
+            if let Body::Bytes(bytes) = &part.body {
+                if self.part_encoding(part).is_none() && parsed.data != *bytes {
+                    return Err(malformed(format!("data of part {:?} changed", part.name)));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_multipart() {
+        let (content_type, body) = MultipartBuilder::new()
+            .with_compact_empty_parts(true)
+            .with_header_folding(Some(30))
+            .add_text("k", "v")
+            .unwrap()
+            .add_text("empty", "")
+            .unwrap()
+            .add_file("file", "test-vector0.txt")
+            .unwrap()
+            .finish()
+            .unwrap();
+        let parts = parse_multipart(&content_type, &body).unwrap();
+        let summary: Vec<_> = parts
+            .iter()
+            .map(|part| {
+                (
+                    part.name.as_str(),
+                    part.filename.as_deref(),
+                    part.content_type.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("k", None, None),
+                ("empty", None, None),
+                ("file", Some("test-vector0.txt"), Some("text/plain")),
+            ]
+        );
+        assert_eq!(parts[0].data, b"v");
+        assert!(parts[1].data.is_empty());
+        assert_eq!(parts[2].data, std::fs::read("test-vector0.txt").unwrap());
+
+        let content_type = "multipart/form-data; boundary=B";
+        let bad = [
+            &b"--B\r\nContent-Disposition: form-data; name=\"k\"\r\n\r\nv"[..],
+            b"preamble\r\n--B--\r\n",
+            b"--B\r\nContent-Disposition: attachment; name=\"k\"\r\n\r\nv\r\n--B--\r\n",
+            b"--B\r\nContent-Disposition: form-data; name=\"a\"b\"\r\n\r\nv\r\n--B--\r\n",
+            b"--B\r\nContent-Type: text/plain\r\n\r\nv\r\n--B--\r\n",
+        ];
+        for body in bad {
+            assert!(parse_multipart(content_type, body).is_err(), "{body:?}");
+        }
+        assert_eq!(parse_multipart(content_type, b"--B--\r\n"), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_self_verify() {
+        let builder = |escape| {
+            MultipartBuilder::new()
+                .with_name_escaping(escape)
+                .add_text("quote\"d", "v")
+                .unwrap()
+                .add_bytes("f", Some("a\r\nb.txt"), None, b"data")
+                .unwrap()
+        };
+        assert_eq!(builder(true).self_verify(), Ok(()));
+        let (_, body) = builder(true).finish().unwrap();
+        let body = String::from_utf8(body).unwrap();
+        assert!(body.contains("name=\"quote%22d\""));
+        assert!(body.contains("filename=\"a%0D%0Ab.txt\""));
+
+        let err = builder(false).self_verify().unwrap_err();
+        assert!(matches!(err, MultipartError::MalformedBody { .. }));
+
+        // names are written as is by default
+        let (_, body) = MultipartBuilder::new()
+            .add_text("quote\"d", "v")
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert!(String::from_utf8(body)
+            .unwrap()
+            .contains("name=\"quote\"d\""));
+    }
+}
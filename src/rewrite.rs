@@ -0,0 +1,200 @@
+use crate::{check_header, MultipartBuilder};
+use mime::Mime;
+use std::fmt;
+use std::io;
+
+/// the extra headers of a part about to be written, with what it is, see
+/// [`MultipartBuilder::with_header_rewriter`]
+///
+/// Header names are compared case insensitively.
+#[derive(Debug)]
+pub struct PartHeaders<'a> {
+    pub(crate) index: usize,
+    pub(crate) name: &'a str,
+    pub(crate) filename: Option<&'a str>,
+    pub(crate) content_type: Option<&'a Mime>,
+    pub(crate) headers: Vec<(String, String)>,
+}
+
+impl PartHeaders<'_> {
+    /// zero based position of the part in the body
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    /// field name
+    pub fn name(&self) -> &str {
+        self.name
+    }
+    /// file name, if any
+    pub fn filename(&self) -> Option<&str> {
+        self.filename
+    }
+    /// content type as written, if any
+    pub fn content_type(&self) -> Option<&Mime> {
+        self.content_type
+    }
+    /// the extra headers, in the order they are written
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+    /// the value of the first header called `name`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+    /// replace every header called `name` by one, added last if there was none
+    pub fn set(&mut self, name: &str, value: &str) {
+        match self
+            .headers
+            .iter()
+            .position(|(n, _)| n.eq_ignore_ascii_case(name))
+        {
+            Some(idx) => {
+                self.headers[idx] = (name.to_string(), value.to_string());
+                let mut seen = 0;
+                self.headers.retain(|(n, _)| {
+                    seen += usize::from(n.eq_ignore_ascii_case(name));
+                    !n.eq_ignore_ascii_case(name) || seen == 1
+                });
+            }
+            None => self.append(name, value),
+        }
+    }
+    /// add a header last, even if one has the same name
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.headers.push((name.to_string(), value.to_string()));
+    }
+    /// remove every header called `name`
+    pub fn remove(&mut self, name: &str) {
+        self.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+    }
+}
+
+type Rewriter = dyn Fn(&mut PartHeaders<'_>) + Send;
+
+pub(crate) struct RewriterBox(Box<Rewriter>);
+
+impl fmt::Debug for RewriterBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HeaderRewriter")
+    }
+}
+
+impl MultipartBuilder {
+    /// change the extra headers of each part right before they are written
+    ///
+    /// The rewriter gets the default and part headers, after the
+    /// [duplicate policy](Self::with_duplicate_header_policy), and can add,
+    /// change or remove them. The `Content-Disposition`, `Content-Type` and
+    /// `Content-Transfer-Encoding` headers aren't part of them. Producing the
+    /// body fails when a rewritten header would break the framing.
+    ///
+    /// It runs every time the headers are written or measured, e.g. for the
+    /// [body length](Self::final_length), and should give the same headers
+    /// each time.
+    pub fn with_header_rewriter<F>(mut self, rewriter: F) -> Self
+    where
+        F: Fn(&mut PartHeaders<'_>) + Send + 'static,
+    {
+        self.header_rewriter = Some(RewriterBox(Box::new(rewriter)));
+        self
+    }
+    /// run the rewriter, if any, and check its headers
+    pub(crate) fn rewrite_headers(&self, headers: &mut PartHeaders<'_>) -> io::Result<()> {
+        let Some(rewriter) = &self.header_rewriter else {
+            return Ok(());
+        };
+        (rewriter.0)(headers);
+        for (name, value) in &headers.headers {
+            check_header(name, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MultipartError;
+
+    #[test]
+    fn test_header_rewriter() {
+        let (_, data) = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .with_default_part_headers(&[("X-Batch", "7")])
+            .with_header_rewriter(|headers| {
+                if let Some(filename) = headers.filename() {
+                    let value = format!("{}-{filename}", headers.index());
+                    headers.set("X-Upload-Name", &value);
+                }
+                if headers.name() == "quiet" {
+                    headers.remove("x-batch");
+                }
+            })
+            .add_text("quiet", "q")
+            .unwrap()
+            .add_bytes("file", Some("a.bin"), None, b"a")
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "--B\r\n\
+            Content-Disposition: form-data; name=\"quiet\"\r\n\
+            \r\n\
+            q\r\n\
+            --B\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\
+            Content-Type: application/octet-stream\r\n\
+            X-Batch: 7\r\n\
+            X-Upload-Name: 1-a.bin\r\n\
+            \r\n\
+            a\r\n\
+            --B--\r\n"
+        );
+    }
+
+    #[test]
+    fn test_header_rewriter_framing() {
+        let err = MultipartBuilder::new()
+            .with_header_rewriter(|headers| headers.append("X-Evil", "a\r\n\r\nb"))
+            .add_text("a", "1")
+            .unwrap()
+            .finish()
+            .unwrap_err();
+        let err = err
+            .into_inner()
+            .unwrap()
+            .downcast::<MultipartError>()
+            .unwrap();
+        assert!(matches!(*err, MultipartError::InvalidHeader { .. }));
+    }
+
+    #[test]
+    fn test_part_headers_set() {
+        let mut headers = PartHeaders {
+            index: 0,
+            name: "a",
+            filename: None,
+            content_type: None,
+            headers: vec![
+                ("X-A".to_string(), "1".to_string()),
+                ("X-B".to_string(), "2".to_string()),
+                ("x-a".to_string(), "3".to_string()),
+            ],
+        };
+        headers.set("X-a", "4");
+        assert_eq!(
+            headers.headers(),
+            [
+                ("X-a".to_string(), "4".to_string()),
+                ("X-B".to_string(), "2".to_string())
+            ]
+        );
+        assert_eq!(headers.get("x-b"), Some("2"));
+        assert_eq!(headers.get("x-c"), None);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::MultipartBuilder;
+use std::fmt::Write;
+
+/// how filenames are written in the `Content-Disposition`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FilenameEncoding {
+    /// as is, UTF-8 included, like browsers do
+    #[default]
+    Raw,
+    /// non ASCII filenames get an RFC 5987 `filename*=UTF-8''` parameter,
+    /// after a `filename` where each non ASCII char is replaced by `_`
+    Rfc5987,
+}
+
+impl MultipartBuilder {
+    /// how filenames are written, as is by default
+    pub fn with_filename_encoding(mut self, encoding: FilenameEncoding) -> Self {
+        self.filename_encoding = encoding;
+        self
+    }
+}
+
+/// the RFC 5987 `ext-value` of a UTF-8 string, every byte that isn't an
+/// `attr-char` is percent encoded
+pub(crate) fn ext_value(value: &str) -> String {
+    let mut out = String::from("UTF-8''");
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            write!(out, "%{byte:02X}").expect("write to a string");
+        }
+    }
+    out
+}
+
+/// an ASCII stand-in for a filename, one `_` per non ASCII char
+pub(crate) fn ascii_fallback(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_ascii() { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn disposition(filename: &str) -> String {
+        let (_, data) = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .with_filename_encoding(FilenameEncoding::Rfc5987)
+            .with_name_escaping(true)
+            .add_bytes("f", Some(filename), None, b"")
+            .unwrap()
+            .finish()
+            .unwrap();
+        let data = String::from_utf8(data).unwrap();
+        data.lines().nth(1).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_ext_value() {
+        // CJK, three bytes per char
+        assert_eq!(ext_value("文件.txt"), "UTF-8''%E6%96%87%E4%BB%B6.txt");
+        // outside the BMP, four bytes, not two escaped surrogates
+        assert_eq!(ext_value("😀"), "UTF-8''%F0%9F%98%80");
+        // a combining accent stays after its letter
+        assert_eq!(ext_value("e\u{301}"), "UTF-8''e%CC%81");
+        assert_eq!(ext_value("a b%\"'*;"), "UTF-8''a%20b%25%22%27%2A%3B");
+        assert_eq!(ext_value("A-z_0.9~`|^!#$&+"), "UTF-8''A-z_0.9~`|^!#$&+");
+    }
+
+    #[test]
+    fn test_rfc5987_filename() {
+        assert_eq!(
+            disposition("😀 文件.png"),
+            "Content-Disposition: form-data; name=\"f\"; filename=\"_ __.png\"; \
+            filename*=UTF-8''%F0%9F%98%80%20%E6%96%87%E4%BB%B6.png"
+        );
+        // punycode is plain ASCII, written once
+        assert_eq!(
+            disposition("xn--bcher-kva.example.txt"),
+            "Content-Disposition: form-data; name=\"f\"; filename=\"xn--bcher-kva.example.txt\""
+        );
+        // the fallback is escaped like any filename
+        assert_eq!(
+            disposition("é\".txt"),
+            "Content-Disposition: form-data; name=\"f\"; filename=\"_%22.txt\"; \
+            filename*=UTF-8''%C3%A9%22.txt"
+        );
+
+        let (_, data) = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_bytes("f", Some("文件"), None, b"")
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert!(String::from_utf8(data)
+            .unwrap()
+            .contains("filename=\"文件\"\r\n"));
+    }
+}
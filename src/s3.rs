@@ -0,0 +1,80 @@
+use crate::{MultipartBuilder, MultipartError};
+use std::io;
+use std::path::Path;
+
+impl MultipartBuilder {
+    /// add the fields and the file of an AWS S3 browser based POST upload
+    ///
+    /// * fields the form fields, e.g. `key`, `policy`, `x-amz-signature`..
+    /// * file_field the file field name, `file` for S3
+    /// * path the file to upload
+    ///
+    /// S3 ignores every field after the file, so the fields are added in the
+    /// given order, then the file, and nothing should be added afterwards.
+    /// The `key` field is required, and fields named like the file are
+    /// rejected.
+    pub fn s3_post<P: AsRef<Path>>(
+        mut self,
+        fields: &[(&str, &str)],
+        file_field: &str,
+        path: P,
+    ) -> io::Result<Self> {
+        if !fields
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("key"))
+        {
+            return Err(MultipartError::MissingField {
+                name: "key".to_string(),
+            }
+            .into());
+        }
+        if fields.iter().any(|(name, _)| *name == file_field) {
+            return Err(MultipartError::ReservedField {
+                name: file_field.to_string(),
+            }
+            .into());
+        }
+        for (name, value) in fields {
+            self = self.add_text(name, value)?;
+        }
+        self.add_file(file_field, path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_s3_post() {
+        let fields = [
+            ("key", "uploads/${filename}"),
+            ("policy", "eyJ..."),
+            ("x-amz-signature", "abcd"),
+        ];
+        let builder = MultipartBuilder::new()
+            .s3_post(&fields, "file", "test-vector0.txt")
+            .unwrap();
+        let names: Vec<_> = builder
+            .parts
+            .iter()
+            .map(|part| part.name.as_str())
+            .collect();
+        assert_eq!(names, ["key", "policy", "x-amz-signature", "file"]);
+        assert!(builder.parts[3].is_file());
+
+        let err = MultipartBuilder::new()
+            .s3_post(&fields[1..], "file", "test-vector0.txt")
+            .unwrap_err();
+        let err = err.into_inner().unwrap().downcast::<MultipartError>();
+        assert_eq!(
+            *err.unwrap(),
+            MultipartError::MissingField {
+                name: "key".to_string()
+            }
+        );
+        assert!(MultipartBuilder::new()
+            .s3_post(&[("key", "k"), ("file", "x")], "file", "test-vector0.txt")
+            .is_err());
+    }
+}
@@ -0,0 +1,73 @@
+use crate::MultipartBuilder;
+use mime::Mime;
+use std::io::{self, Read};
+
+/// bytes peeked to guess the content type of a stream
+const SNIFF_LEN: usize = 8192;
+
+impl MultipartBuilder {
+    /// add some stream, guessing its content type from its first bytes
+    ///
+    /// * stream the data, may be shorter than the sniffed window
+    /// * name field name
+    /// * filename optional file name
+    ///
+    /// Falls back to `application/octet-stream` for unknown data.
+    pub fn add_stream_sniffed<S: Read>(
+        self,
+        stream: &mut S,
+        name: &str,
+        filename: Option<&str>,
+    ) -> io::Result<Self> {
+        let mut peeked = Vec::with_capacity(SNIFF_LEN);
+        stream
+            .by_ref()
+            .take(SNIFF_LEN as u64)
+            .read_to_end(&mut peeked)?;
+        let content_type =
+            infer::get(&peeked).and_then(|kind| kind.mime_type().parse::<Mime>().ok());
+        let mut stream = peeked.as_slice().chain(stream);
+        self.add_stream(&mut stream, name, filename, content_type)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    fn sniffed(data: &[u8]) -> String {
+        let (_, data) = MultipartBuilder::new()
+            .add_stream_sniffed(&mut &data[..], "f", None)
+            .unwrap()
+            .finish()
+            .unwrap();
+        String::from_utf8_lossy(&data).into_owned()
+    }
+
+    #[test]
+    fn test_sniff_png() {
+        let mut png = PNG.to_vec();
+        png.resize(PNG.len() + SNIFF_LEN * 2, 7);
+        let (_, data) = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_stream_sniffed(&mut png.as_slice(), "img", Some("img"))
+            .unwrap()
+            .finish()
+            .unwrap();
+        let mut expected = b"--B\r\n\
+            Content-Disposition: form-data; name=\"img\"; filename=\"img\"\r\n\
+            Content-Type: image/png\r\n\
+            \r\n"
+            .to_vec();
+        expected.extend_from_slice(&png);
+        expected.extend_from_slice(b"\r\n--B--\r\n");
+        assert_eq!(data, expected);
+
+        assert!(sniffed(PNG).contains("Content-Type: image/png\r\n"));
+        assert!(sniffed(b"\x89P").contains("Content-Type: application/octet-stream\r\n"));
+        assert!(sniffed(b"").contains("Content-Type: application/octet-stream\r\n"));
+    }
+}
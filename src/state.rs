@@ -0,0 +1,293 @@
+use crate::{
+    Body, DispositionStyle, DuplicateHeaderPolicy, FilenameEncoding, MissingFilenamePolicy,
+    MultipartBuilder, MultipartError, Part, SymlinkMode, TransferEncoding,
+};
+use mime::Mime;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// the configuration and parts of a builder, to save and restore it later,
+/// see [`MultipartBuilder::to_state`]
+///
+/// Files are saved as paths, in memory data as is. It can be serialized with
+/// any serde format, e.g. JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuilderState {
+    boundary_prefix: String,
+    boundary: String,
+    boundary_alphabet: Vec<u8>,
+    boundary_fixed: bool,
+    boundary_from_names: bool,
+    max_boundary_attempts: usize,
+    parts: Vec<PartState>,
+    compact_empty_parts: bool,
+    disposition_style: DispositionStyle,
+    pad_to: Option<usize>,
+    max_nesting_depth: usize,
+    default_part_headers: Vec<(String, String)>,
+    default_transfer_encoding: Option<TransferEncoding>,
+    part_ids: bool,
+    default_content_type: Option<String>,
+    missing_filename: MissingFilenamePolicy,
+    fold_headers_at: Option<usize>,
+    duplicate_headers: DuplicateHeaderPolicy,
+    escape_names: bool,
+    symlink_mode: SymlinkMode,
+    filename_encoding: FilenameEncoding,
+    max_filename_len: Option<usize>,
+    open_attempts: usize,
+    open_backoff: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct PartState {
+    name: String,
+    filename: Option<String>,
+    content_type: Option<String>,
+    headers: Vec<(String, String)>,
+    transfer_encoding: Option<TransferEncoding>,
+    id: Option<String>,
+    order_key: Option<String>,
+    body: BodyState,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum BodyState {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+    Nested(Box<BuilderState>),
+    Placeholder,
+}
+
+impl MultipartBuilder {
+    /// save the configuration and parts, e.g. for an upload queue that
+    /// survives restarts
+    ///
+    /// Fails for parts that can't be read again, i.e. streams and memory
+    /// mapped ranges. A [boundary source](Self::with_boundary_source) and a
+    /// [header rewriter](Self::with_header_rewriter) aren't saved, set them
+    /// again after [`from_state`](Self::from_state).
+    pub fn to_state(&self) -> io::Result<BuilderState> {
+        let parts = self
+            .parts
+            .iter()
+            .map(|part| {
+                let body = match &part.body {
+                    Body::Bytes(bytes) => BodyState::Bytes(bytes.clone()),
+                    Body::File(path) => BodyState::File(path.clone()),
+                    Body::Nested(builder) => BodyState::Nested(Box::new(builder.to_state()?)),
+                    Body::Placeholder => BodyState::Placeholder,
+                    _ => {
+                        return Err(MultipartError::NotResumable {
+                            name: part.name.clone(),
+                        }
+                        .into())
+                    }
+                };
+                Ok(PartState {
+                    name: part.name.clone(),
+                    filename: part.filename.clone(),
+                    content_type: part.content_type.as_ref().map(Mime::to_string),
+                    headers: part.headers.clone(),
+                    transfer_encoding: part.transfer_encoding,
+                    id: part.id.clone(),
+                    order_key: part.order_key.clone(),
+                    body,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(BuilderState {
+            boundary_prefix: self.boundary_prefix.clone(),
+            boundary: self.boundary.clone(),
+            boundary_alphabet: self.boundary_alphabet.clone(),
+            boundary_fixed: self.boundary_fixed,
+            boundary_from_names: self.boundary_from_names,
+            max_boundary_attempts: self.max_boundary_attempts,
+            parts,
+            compact_empty_parts: self.compact_empty_parts,
+            disposition_style: self.disposition_style,
+            pad_to: self.pad_to,
+            max_nesting_depth: self.max_nesting_depth,
+            default_part_headers: self.default_part_headers.clone(),
+            default_transfer_encoding: self.default_transfer_encoding,
+            part_ids: self.part_ids,
+            default_content_type: self.default_content_type.as_ref().map(Mime::to_string),
+            missing_filename: self.missing_filename,
+            fold_headers_at: self.fold_headers_at,
+            duplicate_headers: self.duplicate_headers,
+            escape_names: self.escape_names,
+            symlink_mode: self.symlink_mode,
+            filename_encoding: self.filename_encoding,
+            max_filename_len: self.max_filename_len,
+            open_attempts: self.open_attempts,
+            open_backoff: self.open_backoff,
+        })
+    }
+    /// restore a builder saved with [`to_state`](Self::to_state)
+    ///
+    /// Files are opened again to report errors early, like
+    /// [`add_file`](Self::add_file) does, and the saved state is checked as
+    /// if it was built again.
+    pub fn from_state(state: BuilderState) -> io::Result<Self> {
+        let mut builder = MultipartBuilder::new();
+        builder.boundary_prefix = state.boundary_prefix;
+        builder.boundary = state.boundary;
+        let alphabet = String::from_utf8(state.boundary_alphabet).map_err(|err| {
+            MultipartError::InvalidBoundary {
+                boundary: String::from_utf8_lossy(err.as_bytes()).into_owned(),
+                reason: "alphabet isn't UTF-8".to_string(),
+            }
+        })?;
+        crate::check_alphabet(&alphabet)?;
+        builder.boundary_alphabet = alphabet.into_bytes();
+        builder.boundary_fixed = state.boundary_fixed;
+        builder.boundary_from_names = state.boundary_from_names;
+        builder.max_boundary_attempts = state.max_boundary_attempts;
+        builder.compact_empty_parts = state.compact_empty_parts;
+        builder.disposition_style = state.disposition_style;
+        builder.pad_to = state.pad_to;
+        builder.max_nesting_depth = state.max_nesting_depth;
+        builder.default_part_headers = state.default_part_headers;
+        builder.default_transfer_encoding = state.default_transfer_encoding;
+        builder.part_ids = state.part_ids;
+        builder.default_content_type = state
+            .default_content_type
+            .as_deref()
+            .map(parse_mime)
+            .transpose()?;
+        builder.missing_filename = state.missing_filename;
+        builder.fold_headers_at = state.fold_headers_at;
+        builder.duplicate_headers = state.duplicate_headers;
+        builder.escape_names = state.escape_names;
+        builder.symlink_mode = state.symlink_mode;
+        builder.filename_encoding = state.filename_encoding;
+        builder.max_filename_len = state.max_filename_len;
+        builder.open_attempts = state.open_attempts;
+        builder.open_backoff = state.open_backoff;
+        crate::check_boundary(&format!("{}{}", builder.boundary_prefix, builder.boundary))?;
+        for part in state.parts {
+            let body = match part.body {
+                BodyState::Bytes(bytes) => Body::Bytes(bytes),
+                BodyState::File(path) => {
+                    builder.check_open(&path)?;
+                    Body::File(path)
+                }
+                BodyState::Nested(state) => Body::Nested(Box::new(Self::from_state(*state)?)),
+                BodyState::Placeholder => Body::Placeholder,
+            };
+            for (name, value) in &part.headers {
+                crate::check_header(name, value)?;
+            }
+            builder.parts.push(Part {
+                name: part.name,
+                filename: part.filename,
+                content_type: part.content_type.as_deref().map(parse_mime).transpose()?,
+                headers: part.headers,
+                transfer_encoding: part.transfer_encoding,
+                id: part.id,
+                order_key: part.order_key,
+                body,
+            });
+        }
+        builder.avoid_collisions()?;
+        Ok(builder)
+    }
+}
+
+fn parse_mime(content_type: &str) -> io::Result<Mime> {
+    content_type.parse().map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{content_type:?}: {err}"),
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_state_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("ureq-multipart-state-{}.txt", std::process::id()));
+        fs::write(&path, "file content").unwrap();
+        let inner = MultipartBuilder::new()
+            .with_boundary("inner")
+            .unwrap()
+            .add_text("k", "v")
+            .unwrap();
+        let builder = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .with_part_ids(true)
+            .with_default_part_headers(&[("X-Job", "42")])
+            .with_open_retries(3, Duration::from_millis(5))
+            .add_text("title", "report")
+            .unwrap()
+            .add_file("upload", &path)
+            .unwrap()
+            .add_multipart("batch", "mixed", inner)
+            .unwrap();
+        let state = builder.to_state().unwrap();
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(json.contains(&format!("{:?}", path.display().to_string())));
+        assert!(!json.contains("file content"));
+        let restored: BuilderState = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, state);
+        let restored = MultipartBuilder::from_state(restored).unwrap();
+        assert_eq!(restored.open_attempts, 3);
+        assert_eq!(restored.build().unwrap(), builder.build().unwrap());
+
+        // the file is gone, the part can't be restored
+        fs::remove_file(&path).unwrap();
+        let err = MultipartBuilder::from_state(state).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_state_invalid_alphabet() {
+        let state = MultipartBuilder::new().to_state().unwrap();
+        let mut json: serde_json::Value = serde_json::to_value(&state).unwrap();
+        for alphabet in [
+            serde_json::json!([]),
+            serde_json::json!([b'a', b' ']),
+            serde_json::json!([0xff]),
+        ] {
+            json["boundary_alphabet"] = alphabet;
+            let state: BuilderState = serde_json::from_value(json.clone()).unwrap();
+            let err = MultipartBuilder::from_state(state).unwrap_err();
+            let err = err
+                .into_inner()
+                .unwrap()
+                .downcast::<MultipartError>()
+                .unwrap();
+            assert!(matches!(*err, MultipartError::InvalidBoundary { .. }));
+        }
+    }
+
+    #[test]
+    fn test_state_one_shot_part() {
+        let err = MultipartBuilder::new()
+            .add_text("a", "1")
+            .unwrap()
+            .add_reader("stream", None, None, io::empty())
+            .unwrap()
+            .to_state()
+            .unwrap_err();
+        let err = err
+            .into_inner()
+            .unwrap()
+            .downcast::<MultipartError>()
+            .unwrap();
+        assert_eq!(
+            *err,
+            MultipartError::NotResumable {
+                name: "stream".to_string()
+            }
+        );
+    }
+}
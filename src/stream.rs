@@ -0,0 +1,991 @@
+use crate::open_retry::FileOpener;
+use crate::{Body, MultipartBuilder, MultipartError, Part, TransferEncoding};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Cursor, Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// the multipart body as a stream, see [`MultipartBuilder::into_reader`]
+///
+/// Files and streams are only read as the body is consumed.
+pub struct MultipartReader {
+    segments: VecDeque<Segment>,
+    deadline: Option<Instant>,
+    opener: FileOpener,
+    #[cfg(feature = "sha2")]
+    hasher: Option<sha2::Sha256>,
+    #[cfg(feature = "sha2")]
+    sha256: Option<[u8; 32]>,
+    /// the part name of each segment holding part data
+    #[cfg(feature = "crc32fast")]
+    pub(crate) part_names: VecDeque<Option<String>>,
+    #[cfg(feature = "crc32fast")]
+    pub(crate) crc_hasher: Option<crc32fast::Hasher>,
+    #[cfg(feature = "crc32fast")]
+    pub(crate) crcs: Option<Vec<(String, u32)>>,
+}
+
+enum Segment {
+    Bytes(Cursor<Vec<u8>>),
+    File(PathBuf),
+    Reader(Box<dyn Read + Send>),
+}
+
+impl Part {
+    /// a stream over the part data, failing for one-shot streams
+    #[cfg(any(feature = "sha2", feature = "md5"))]
+    pub(crate) fn data_reader(&self) -> io::Result<MultipartReader> {
+        let segment = self.body.replay_segment(&self.name)?;
+        Ok(MultipartReader::from_segments(VecDeque::from([segment])))
+    }
+}
+
+impl Body {
+    fn into_segment(self, name: &str) -> io::Result<Segment> {
+        Ok(match self {
+            Body::Bytes(bytes) => Segment::Bytes(Cursor::new(bytes)),
+            Body::File(path) => Segment::File(path),
+            Body::Reader(reader, _) => Segment::Reader(reader),
+            Body::Nested(builder) => Segment::Reader(Box::new(builder.into_reader()?.1)),
+            #[cfg(feature = "mmap")]
+            Body::Mmap(range) => Segment::Reader(Box::new(Cursor::new(range))),
+            Body::Placeholder => return Err(unfilled(name)),
+        })
+    }
+    /// a segment reading the body again, failing for one-shot streams
+    fn replay_segment(&self, name: &str) -> io::Result<Segment> {
+        Ok(match self {
+            Body::Bytes(bytes) => Segment::Bytes(Cursor::new(bytes.clone())),
+            Body::File(path) => Segment::File(path.clone()),
+            Body::Reader(..) => {
+                return Err(MultipartError::OneShotPart {
+                    name: name.to_string(),
+                }
+                .into())
+            }
+            Body::Nested(builder) => Segment::Reader(Box::new(builder.replay_reader()?)),
+            #[cfg(feature = "mmap")]
+            Body::Mmap(range) => Segment::Reader(Box::new(Cursor::new(range.clone()))),
+            Body::Placeholder => return Err(unfilled(name)),
+        })
+    }
+}
+
+fn unfilled(name: &str) -> io::Error {
+    MultipartError::UnfilledPlaceholder {
+        name: name.to_string(),
+    }
+    .into()
+}
+
+/// wrap a part body into its transfer encoding
+#[cfg_attr(not(feature = "base64"), allow(unused_variables))]
+fn encode(segment: Segment, encoding: Option<TransferEncoding>, opener: &FileOpener) -> Segment {
+    match encoding {
+        #[cfg(feature = "base64")]
+        Some(TransferEncoding::Base64) => {
+            let mut reader = MultipartReader::from_segments(VecDeque::from([segment]));
+            reader.opener = opener.clone();
+            Segment::Reader(Box::new(Base64Reader {
+                inner: reader,
+                raw: Vec::new(),
+                encoded: Cursor::new(Vec::new()),
+                lines: false,
+                eof: false,
+            }))
+        }
+        _ => segment,
+    }
+}
+
+/// input bytes per base64 line, encoded as the 76 chars RFC 2045 allows
+#[cfg(feature = "base64")]
+pub(crate) const BASE64_LINE_BYTES: usize = 57;
+
+/// base64 encodes a stream as it's read, in lines of 76 chars
+#[cfg(feature = "base64")]
+struct Base64Reader<R> {
+    inner: R,
+    /// read but not yet encoded data, always less than a line between reads
+    raw: Vec<u8>,
+    encoded: Cursor<Vec<u8>>,
+    /// whether a line was encoded already, the next one starts with a CRLF
+    lines: bool,
+    eof: bool,
+}
+
+#[cfg(feature = "base64")]
+impl<R: Read> Read for Base64Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use base64::Engine;
+        loop {
+            let read = self.encoded.read(buf)?;
+            if read > 0 || buf.is_empty() || self.eof {
+                return Ok(read);
+            }
+            let mut chunk = [0; BASE64_LINE_BYTES * 64];
+            let len = self.inner.read(&mut chunk)?;
+            self.raw.extend_from_slice(&chunk[..len]);
+            // only whole lines can be encoded before the end
+            let split = if len == 0 {
+                self.eof = true;
+                self.raw.len()
+            } else {
+                self.raw.len() - self.raw.len() % BASE64_LINE_BYTES
+            };
+            let rest = self.raw.split_off(split);
+            let engine = base64::engine::general_purpose::STANDARD;
+            let mut encoded = Vec::new();
+            for line in self.raw.chunks(BASE64_LINE_BYTES) {
+                if self.lines {
+                    encoded.extend_from_slice(b"\r\n");
+                }
+                encoded.extend_from_slice(engine.encode(line).as_bytes());
+                self.lines = true;
+            }
+            self.encoded = Cursor::new(encoded);
+            self.raw = rest;
+        }
+    }
+}
+
+/// byte counts of a body, see [`MultipartBuilder::framing_stats`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FramingStats {
+    /// number of parts
+    pub parts: usize,
+    /// part headers, including the blank line ending them
+    pub header_bytes: u64,
+    /// part data, `None` when a stream of unknown length was added
+    pub data_bytes: Option<u64>,
+    /// delimiter lines, including the closing one
+    pub boundary_bytes: u64,
+    /// padding epilogue, see [`MultipartBuilder::pad_to`]
+    pub padding_bytes: u64,
+}
+
+impl Default for FramingStats {
+    fn default() -> Self {
+        Self {
+            parts: 0,
+            header_bytes: 0,
+            data_bytes: Some(0),
+            boundary_bytes: 0,
+            padding_bytes: 0,
+        }
+    }
+}
+
+/// reads exactly `remaining` bytes, failing on a short stream
+pub(crate) struct ExactReader<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R> ExactReader<R> {
+    pub(crate) fn new(inner: R, len: u64) -> Self {
+        Self {
+            inner,
+            remaining: len,
+        }
+    }
+}
+
+impl<R: Read> Read for ExactReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        let max = buf
+            .len()
+            .min(self.remaining.try_into().unwrap_or(usize::MAX));
+        let read = self.inner.read(&mut buf[..max])?;
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("stream ended {} bytes short", self.remaining),
+            ));
+        }
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+/// reads the chunks produced by a closure
+pub(crate) struct GeneratorReader<F> {
+    gen: F,
+    chunk: Cursor<Vec<u8>>,
+    done: bool,
+}
+
+impl<F> GeneratorReader<F> {
+    pub(crate) fn new(gen: F) -> Self {
+        Self {
+            gen,
+            chunk: Cursor::new(Vec::new()),
+            done: false,
+        }
+    }
+}
+
+impl<F: FnMut() -> Option<Vec<u8>>> Read for GeneratorReader<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.chunk.read(buf)?;
+            if read > 0 || buf.is_empty() || self.done {
+                return Ok(read);
+            }
+            match (self.gen)() {
+                Some(chunk) => self.chunk = Cursor::new(chunk),
+                None => self.done = true,
+            }
+        }
+    }
+}
+
+impl MultipartReader {
+    fn new(
+        heads: Vec<Vec<u8>>,
+        bodies: Vec<Segment>,
+        closing: Vec<u8>,
+        #[cfg_attr(not(feature = "crc32fast"), allow(unused_variables))] names: Vec<String>,
+        opener: FileOpener,
+    ) -> Self {
+        let mut segments = VecDeque::with_capacity(heads.len() * 2 + 1);
+        for (head, body) in heads.into_iter().zip(bodies) {
+            segments.push_back(Segment::Bytes(Cursor::new(head)));
+            segments.push_back(body);
+        }
+        segments.push_back(Segment::Bytes(Cursor::new(closing)));
+        let mut reader = Self::from_segments(segments);
+        reader.opener = opener;
+        #[cfg(feature = "crc32fast")]
+        {
+            reader.part_names.clear();
+            for name in names {
+                reader.part_names.extend([None, Some(name)]);
+            }
+            reader.part_names.push_back(None);
+        }
+        reader
+    }
+    fn from_segments(segments: VecDeque<Segment>) -> Self {
+        #[cfg(feature = "crc32fast")]
+        let part_names = VecDeque::from(vec![None; segments.len()]);
+        Self {
+            segments,
+            deadline: None,
+            opener: FileOpener::default(),
+            #[cfg(feature = "sha2")]
+            hasher: None,
+            #[cfg(feature = "sha2")]
+            sha256: None,
+            #[cfg(feature = "crc32fast")]
+            part_names,
+            #[cfg(feature = "crc32fast")]
+            crc_hasher: None,
+            #[cfg(feature = "crc32fast")]
+            crcs: None,
+        }
+    }
+    /// fail reads with a `TimedOut` error once `timeout` has elapsed
+    ///
+    /// The time counts from this call. The deadline is checked every time the
+    /// stream is read, so it bounds the time spent producing the body, e.g. a
+    /// slow stream. A write stalled on the socket is only bounded by ureq's
+    /// own write timeout, and waiting for the response by its read timeout.
+    pub fn with_deadline(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(Instant::now() + timeout);
+        self
+    }
+    /// compute the sha-256 digest of the body as it's read
+    ///
+    /// Meant for protocols sending the digest in a trailer, after the body.
+    /// ureq can't send trailers, the digest can be sent in a later request,
+    /// or the reader used with another http client.
+    #[cfg(feature = "sha2")]
+    pub fn with_sha256(mut self) -> Self {
+        use sha2::Digest;
+        self.hasher = Some(sha2::Sha256::new());
+        self
+    }
+    /// the sha-256 digest of the whole body, once it was read to the end
+    ///
+    /// `None` before that, or without [`with_sha256`](Self::with_sha256).
+    #[cfg(feature = "sha2")]
+    pub fn sha256(&self) -> Option<[u8; 32]> {
+        self.sha256
+    }
+}
+
+impl Read for MultipartReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "multipart upload deadline exceeded",
+            ));
+        }
+        while let Some(segment) = self.segments.front_mut() {
+            let read = match segment {
+                Segment::Bytes(bytes) => bytes.read(buf)?,
+                Segment::File(path) => {
+                    *segment = Segment::Reader(Box::new(self.opener.open(path)?));
+                    continue;
+                }
+                Segment::Reader(reader) => reader.read(buf)?,
+            };
+            if read > 0 || buf.is_empty() {
+                #[cfg(feature = "sha2")]
+                if let Some(hasher) = &mut self.hasher {
+                    sha2::Digest::update(hasher, &buf[..read]);
+                }
+                #[cfg(feature = "crc32fast")]
+                self.update_crc(&buf[..read]);
+                return Ok(read);
+            }
+            self.segments.pop_front();
+            #[cfg(feature = "crc32fast")]
+            self.finish_crc();
+        }
+        #[cfg(feature = "sha2")]
+        if let Some(hasher) = self.hasher.take() {
+            self.sha256 = Some(sha2::Digest::finalize(hasher).into());
+        }
+        Ok(0)
+    }
+}
+
+impl fmt::Debug for MultipartReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultipartReader")
+            .field("segments", &self.segments.len())
+            .finish()
+    }
+}
+
+/// the multipart body in pieces of a fixed size, see
+/// [`MultipartBuilder::finish_chunks`]
+///
+/// Every chunk is full but the last one. The iterator ends after an error.
+#[derive(Debug)]
+pub struct Chunks {
+    reader: Option<MultipartReader>,
+    chunk_size: usize,
+}
+
+impl Iterator for Chunks {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reader = self.reader.as_mut()?;
+        let mut chunk = Vec::with_capacity(self.chunk_size);
+        match reader
+            .by_ref()
+            .take(self.chunk_size as u64)
+            .read_to_end(&mut chunk)
+        {
+            Ok(_) if chunk.len() < self.chunk_size => {
+                self.reader = None;
+                (!chunk.is_empty()).then_some(Ok(chunk))
+            }
+            Ok(_) => Some(Ok(chunk)),
+            Err(err) => {
+                self.reader = None;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// a body that can be read again from the start, see
+/// [`MultipartBuilder::into_retryable`]
+#[derive(Debug)]
+pub struct RetryableBody {
+    builder: MultipartBuilder,
+}
+
+impl RetryableBody {
+    /// http header content type
+    pub fn content_type(&self) -> String {
+        self.builder.content_type()
+    }
+    /// a fresh stream over the whole body, files are opened again
+    pub fn reader(&self) -> io::Result<MultipartReader> {
+        self.builder.replay_reader()
+    }
+}
+
+impl MultipartBuilder {
+    /// general multipart body as a stream
+    ///
+    /// # Return
+    /// * (content_type,reader)
+    ///    * content_type http header content type
+    ///    * reader ureq.req.send(reader)
+    pub fn into_reader(mut self) -> io::Result<(String, MultipartReader)> {
+        let names = self.parts.iter().map(|part| part.name.clone()).collect();
+        let opener = self.file_opener();
+        let heads = self.part_heads()?;
+        let closing = self.closing(&heads)?;
+        let encodings: Vec<_> = self
+            .parts
+            .iter()
+            .map(|part| self.part_encoding(part))
+            .collect();
+        let bodies = self
+            .parts
+            .drain(..)
+            .zip(encodings)
+            .map(|(part, encoding)| {
+                let segment = part.body.into_segment(&part.name)?;
+                Ok(encode(segment, encoding, &opener))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok((
+            self.content_type(),
+            MultipartReader::new(heads, bodies, closing, names, opener),
+        ))
+    }
+    /// general multipart body as chunks of `chunk_size` bytes, e.g. for
+    /// transports with a preferred write size
+    ///
+    /// The body is streamed like with [`into_reader`](Self::into_reader).
+    /// Fails when `chunk_size` is 0.
+    ///
+    /// # Return
+    /// * (content_type,chunks)
+    ///    * content_type http header content type
+    ///    * chunks the body, the last chunk may be shorter
+    pub fn finish_chunks(self, chunk_size: usize) -> io::Result<(String, Chunks)> {
+        if chunk_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "chunk size must not be 0",
+            ));
+        }
+        let (content_type, reader) = self.into_reader()?;
+        let chunks = Chunks {
+            reader: Some(reader),
+            chunk_size,
+        };
+        Ok((content_type, chunks))
+    }
+    /// write the multipart body to any writer, e.g. a socket
+    ///
+    /// This doesn't need ureq, e.g. to upload over a `UnixStream` to a local
+    /// service. The body is streamed, files and streams are read as it goes.
+    ///
+    /// # Return
+    /// * (content_type,written)
+    ///    * content_type http header content type
+    ///    * written number of bytes written
+    pub fn finish_into_writer<W: Write>(self, writer: &mut W) -> io::Result<(String, u64)> {
+        let (content_type, mut reader) = self.into_reader()?;
+        let written = io::copy(&mut reader, writer)?;
+        writer.flush()?;
+        Ok((content_type, written))
+    }
+    /// whether the body can be read more than once, i.e. it holds no stream
+    /// added with [`add_reader`](Self::add_reader)
+    pub fn is_retryable(&self) -> bool {
+        self.one_shot_part().is_none()
+    }
+    /// a body that can be sent again, e.g. with
+    /// [`send_multipart_retrying`](crate::MultipartRequest::send_multipart_retrying)
+    ///
+    /// Fails when the body isn't [retryable](Self::is_retryable).
+    pub fn into_retryable(self) -> io::Result<RetryableBody> {
+        if let Some(name) = self.one_shot_part() {
+            return Err(MultipartError::OneShotPart {
+                name: name.to_string(),
+            }
+            .into());
+        }
+        Ok(RetryableBody { builder: self })
+    }
+    /// general multipart data, leaving the builder reusable
+    ///
+    /// Like [`finish`](Self::finish), but can be called any number of times,
+    /// e.g. to send the same body again. Files are read again on every call,
+    /// and it fails when the body isn't [retryable](Self::is_retryable).
+    ///
+    /// # Return
+    /// * (content_type,post_data)
+    pub fn build(&self) -> io::Result<(String, Vec<u8>)> {
+        let mut data = Vec::new();
+        self.replay_reader()?.read_to_end(&mut data)?;
+        Ok((self.content_type(), data))
+    }
+    /// a stream over the body that leaves the parts in place
+    pub(crate) fn replay_reader(&self) -> io::Result<MultipartReader> {
+        let opener = self.file_opener();
+        let bodies = self
+            .parts
+            .iter()
+            .map(|part| {
+                let segment = part.body.replay_segment(&part.name)?;
+                Ok(encode(segment, self.part_encoding(part), &opener))
+            })
+            .collect::<io::Result<_>>()?;
+        let heads = self.part_heads()?;
+        let closing = self.closing(&heads)?;
+        let names = self.parts.iter().map(|part| part.name.clone()).collect();
+        Ok(MultipartReader::new(heads, bodies, closing, names, opener))
+    }
+    fn one_shot_part(&self) -> Option<&str> {
+        self.parts.iter().find_map(|part| match &part.body {
+            Body::Reader(..) => Some(part.name.as_str()),
+            Body::Nested(builder) => builder.one_shot_part(),
+            _ => None,
+        })
+    }
+    /// length of the whole body, when known without reading it
+    ///
+    /// In memory data and files count for their exact length, `None` means a
+    /// stream of unknown length was added, and the body has to be sent
+    /// chunked. Otherwise it can be sent with a `Content-Length`.
+    pub fn final_length(&self) -> io::Result<Option<u64>> {
+        let heads = self.part_heads()?;
+        let mut len = heads.iter().map(Vec::len).sum::<usize>() as u64;
+        for part in &self.parts {
+            match self.part_len(part)? {
+                Some(part_len) => len += part_len,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(len + self.closing(&heads)?.len() as u64))
+    }
+    /// how the body size splits between framing and data, without reading it
+    pub fn framing_stats(&self) -> io::Result<FramingStats> {
+        let heads = self.part_heads()?;
+        let delimiter_line = self.delimiter().len() as u64 + 2;
+        let mut stats = FramingStats {
+            parts: self.parts.len(),
+            ..FramingStats::default()
+        };
+        for (idx, head) in heads.iter().enumerate() {
+            let crlf = if idx > 0 { 2 } else { 0 };
+            let boundary = crlf + delimiter_line;
+            stats.boundary_bytes += boundary;
+            stats.header_bytes += head.len() as u64 - boundary;
+        }
+        for part in &self.parts {
+            stats.data_bytes = match (stats.data_bytes, self.part_len(part)?) {
+                (Some(total), Some(len)) => Some(total + len),
+                _ => None,
+            };
+        }
+        let crlf = if self.parts.is_empty() { 0 } else { 2 };
+        let closing = crlf + delimiter_line + 2;
+        stats.boundary_bytes += closing;
+        if self.pad_to.is_some() && stats.data_bytes.is_some() {
+            stats.padding_bytes = self.closing(&heads)?.len() as u64 - closing;
+        }
+        Ok(stats)
+    }
+    /// delimiter and headers of every part, each but the first preceded by
+    /// the CRLF ending the previous part
+    fn part_heads(&self) -> io::Result<Vec<Vec<u8>>> {
+        self.parts
+            .iter()
+            .enumerate()
+            .map(|(idx, part)| {
+                let mut head = Vec::new();
+                if idx > 0 {
+                    head.write_all(b"\r\n")?;
+                }
+                self.write_part_headers(&mut head, idx, part)?;
+                Ok(head)
+            })
+            .collect()
+    }
+    /// the closing delimiter, always written even for empty bodies, followed
+    /// by the padding epilogue if any
+    fn closing(&self, heads: &[Vec<u8>]) -> io::Result<Vec<u8>> {
+        let crlf = if self.parts.is_empty() { "" } else { "\r\n" };
+        let mut closing = format!("{crlf}{}--\r\n", self.delimiter()).into_bytes();
+        if let Some(target) = self.pad_to {
+            let mut len = heads.iter().map(Vec::len).sum::<usize>() as u64 + closing.len() as u64;
+            for part in &self.parts {
+                len += self
+                    .part_len(part)?
+                    .ok_or_else(|| MultipartError::UnknownLength {
+                        name: part.name.clone(),
+                    })?;
+            }
+            if len > target as u64 {
+                return Err(MultipartError::PadTargetExceeded { len, target }.into());
+            }
+            closing.resize(closing.len() + (target as u64 - len) as usize, b' ');
+        }
+        Ok(closing)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{response, serve};
+    use crate::MultipartRequest;
+
+    #[test]
+    fn test_reader_matches_finish() {
+        let build = || {
+            let mut builder = MultipartBuilder::new();
+            builder.boundary = "B".into();
+            builder
+                .add_text("k", "v")
+                .unwrap()
+                .add_file("f", "test-vector0.txt")
+                .unwrap()
+                .add_reader("r", Some("r.bin"), None, "streamed".as_bytes())
+                .unwrap()
+        };
+        let (content_type, mut reader) = build().into_reader().unwrap();
+        let mut streamed = Vec::new();
+        // a tiny buffer to cross the segments mid read
+        let mut buf = [0; 3];
+        loop {
+            let read = reader.read(&mut buf).unwrap();
+            if read == 0 {
+                break;
+            }
+            streamed.extend_from_slice(&buf[..read]);
+        }
+        assert_eq!((content_type, streamed), build().finish().unwrap());
+    }
+
+    #[test]
+    fn test_build_twice() {
+        let builder = MultipartBuilder::new()
+            .add_text("k", "v")
+            .unwrap()
+            .add_file("f", "test-vector0.txt")
+            .unwrap();
+        let first = builder.build().unwrap();
+        assert_eq!(first, builder.build().unwrap());
+        assert_eq!(first, builder.finish().unwrap());
+
+        let builder = MultipartBuilder::new()
+            .add_reader("r", None, None, io::empty())
+            .unwrap();
+        assert!(builder.build().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_finish_into_unix_stream() {
+        use std::os::unix::net::UnixStream;
+        use std::thread;
+
+        let build = || {
+            MultipartBuilder::new()
+                .with_boundary("B")
+                .unwrap()
+                .add_text("k", "v")
+                .unwrap()
+                .add_file("f", "test-vector0.txt")
+                .unwrap()
+        };
+        let (mut tx, mut rx) = UnixStream::pair().unwrap();
+        let sender = thread::spawn(move || build().finish_into_writer(&mut tx).unwrap());
+        let mut received = Vec::new();
+        rx.read_to_end(&mut received).unwrap();
+
+        let (content_type, written) = sender.join().unwrap();
+        assert_eq!(written, received.len() as u64);
+        assert_eq!((content_type, received), build().finish().unwrap());
+    }
+
+    #[test]
+    fn test_pad_to() {
+        let build = || {
+            MultipartBuilder::new()
+                .with_boundary("B")
+                .unwrap()
+                .add_text("k", "v")
+                .unwrap()
+                .add_file("f", "test-vector0.txt")
+                .unwrap()
+        };
+        let (_, unpadded) = build().finish().unwrap();
+        let (_, data) = build().pad_to(500).finish().unwrap();
+        assert_eq!(data.len(), 500);
+        assert_eq!(&data[..unpadded.len()], unpadded);
+        assert!(data[unpadded.len()..].iter().all(|&b| b == b' '));
+
+        let (_, data) = build().pad_to(unpadded.len()).build().unwrap();
+        assert_eq!(data, unpadded);
+
+        let err = build().pad_to(unpadded.len() - 1).finish().unwrap_err();
+        let err = err
+            .into_inner()
+            .unwrap()
+            .downcast::<MultipartError>()
+            .unwrap();
+        assert_eq!(
+            *err,
+            MultipartError::PadTargetExceeded {
+                len: unpadded.len() as u64,
+                target: unpadded.len() - 1
+            }
+        );
+
+        let err = build()
+            .add_reader("r", None, None, io::empty())
+            .unwrap()
+            .pad_to(500)
+            .finish()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    /// yields a byte every 10ms
+    struct Slow(usize);
+
+    impl Read for Slow {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0 == 0 || buf.is_empty() {
+                return Ok(0);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+            self.0 -= 1;
+            buf[0] = b'.';
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_deadline() {
+        let slow = |bytes| {
+            MultipartBuilder::new()
+                .add_reader("slow", None, None, Slow(bytes))
+                .unwrap()
+        };
+        let (_, reader) = slow(100).into_reader().unwrap();
+        let err = reader
+            .with_deadline(Duration::from_millis(100))
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        let (_, reader) = slow(3).into_reader().unwrap();
+        let mut data = Vec::new();
+        reader
+            .with_deadline(Duration::from_secs(10))
+            .read_to_end(&mut data)
+            .unwrap();
+        assert!(data.windows(9).any(|w| w == b"\r\n\r\n...\r\n"));
+
+        let url = serve(|req, _| response(200, &[], &req.body));
+        let err = ureq::post(&url)
+            .send_multipart_with_deadline(slow(100), Duration::from_millis(100))
+            .unwrap_err();
+        assert!(matches!(err, ureq::Error::Transport(_)));
+        let resp = ureq::post(&url)
+            .send_multipart_with_deadline(slow(3), Duration::from_secs(10))
+            .unwrap();
+        assert!(resp.into_string().unwrap().contains("\r\n\r\n...\r\n"));
+    }
+
+    #[test]
+    fn test_final_length() {
+        let buf = b"0123456789";
+        let builder = MultipartBuilder::new()
+            .add_text("k", "v")
+            .unwrap()
+            .add_bytes("slice", Some("s.bin"), None, &buf[2..5])
+            .unwrap()
+            .add_bytes("empty", None, None, &[])
+            .unwrap();
+        let len = builder.final_length().unwrap();
+        assert_eq!(len, Some(builder.build().unwrap().1.len() as u64));
+
+        let builder = builder.add_reader("r", None, None, io::empty()).unwrap();
+        assert_eq!(builder.final_length().unwrap(), None);
+    }
+
+    #[test]
+    fn test_sized_reader() {
+        /// wraps around a small buffer, a few bytes per read
+        struct Ring(Vec<u8>, usize);
+        impl Read for Ring {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let len = buf.len().min(3);
+                for b in &mut buf[..len] {
+                    *b = self.0[self.1 % self.0.len()];
+                    self.1 += 1;
+                }
+                Ok(len)
+            }
+        }
+        let builder = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_sized_reader("ring", None, None, Ring(b"abcd".to_vec(), 0), 10)
+            .unwrap();
+        let len = builder.final_length().unwrap();
+        let (_, data) = builder.finish().unwrap();
+        assert_eq!(len, Some(data.len() as u64));
+        let expected = "--B\r\nContent-Disposition: form-data; name=\"ring\"\r\n\
+            Content-Type: application/octet-stream\r\n\r\nabcdabcdab\r\n--B--\r\n";
+        assert_eq!(String::from_utf8(data).unwrap(), expected);
+
+        let err = MultipartBuilder::new()
+            .add_sized_reader("short", None, None, Cursor::new(b"abc".to_vec()), 4)
+            .unwrap()
+            .finish()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn test_body_sha256() {
+        use sha2::{Digest, Sha256};
+
+        let (_, mut reader) = MultipartBuilder::new()
+            .add_text("k", "v")
+            .unwrap()
+            .add_file("file", "test-vector0.txt")
+            .unwrap()
+            .into_reader()
+            .unwrap();
+        reader = reader.with_sha256();
+        let mut data = Vec::new();
+        let mut buf = [0; 7];
+        loop {
+            assert_eq!(reader.sha256(), None);
+            let read = reader.read(&mut buf).unwrap();
+            if read == 0 {
+                break;
+            }
+            data.extend_from_slice(&buf[..read]);
+        }
+        assert_eq!(reader.sha256(), Some(Sha256::digest(&data).into()));
+    }
+
+    #[test]
+    fn test_framing_stats() {
+        let builder = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_text("k", "value")
+            .unwrap()
+            .add_file("file", "test-vector0.txt")
+            .unwrap()
+            .pad_to(1024);
+        let stats = builder.framing_stats().unwrap();
+        let file_len = std::fs::metadata("test-vector0.txt").unwrap().len();
+        assert_eq!(stats.parts, 2);
+        assert_eq!(stats.data_bytes, Some(5 + file_len));
+        // "--B\r\n", "\r\n--B\r\n" and "\r\n--B--\r\n"
+        assert_eq!(stats.boundary_bytes, 5 + 7 + 9);
+        let (_, data) = builder.finish().unwrap();
+        let total = stats.header_bytes
+            + stats.data_bytes.unwrap()
+            + stats.boundary_bytes
+            + stats.padding_bytes;
+        assert_eq!(total, data.len() as u64);
+        assert_eq!(total, 1024);
+
+        let stats = MultipartBuilder::new().framing_stats().unwrap();
+        assert_eq!(stats.parts, 0);
+        assert_eq!(stats.header_bytes, 0);
+        assert_eq!(
+            stats.boundary_bytes,
+            MultipartBuilder::new().finish().unwrap().1.len() as u64
+        );
+    }
+
+    #[test]
+    fn test_finish_chunks() {
+        let builder = || {
+            MultipartBuilder::new()
+                .with_boundary("B")
+                .unwrap()
+                .add_text("a", "1")
+                .unwrap()
+                .add_reader("b", None, None, io::repeat(b'x').take(1000))
+                .unwrap()
+        };
+        let (_, expected) = builder().finish().unwrap();
+        for chunk_size in [1, 7, 64, expected.len(), expected.len() + 1] {
+            let (_, chunks) = builder().finish_chunks(chunk_size).unwrap();
+            let chunks: Vec<_> = chunks.collect::<io::Result<_>>().unwrap();
+            let (last, full) = chunks.split_last().unwrap();
+            assert!(full.iter().all(|chunk| chunk.len() == chunk_size));
+            assert!(!last.is_empty() && last.len() <= chunk_size);
+            assert_eq!(chunks.len(), expected.len().div_ceil(chunk_size));
+            assert_eq!(chunks.concat(), expected);
+        }
+
+        let err = builder().finish_chunks(0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let (_, mut chunks) = MultipartBuilder::new()
+            .add_sized_reader("short", None, None, &b""[..], 10)
+            .unwrap()
+            .finish_chunks(4096)
+            .unwrap();
+        assert!(chunks.next().unwrap().is_err());
+        assert!(chunks.next().is_none());
+    }
+
+    #[test]
+    fn test_generator() {
+        let mut chunks = vec![b"c".to_vec(), Vec::new(), b"b".to_vec(), b"a".to_vec()];
+        let builder = MultipartBuilder::new()
+            .with_boundary("B")
+            .unwrap()
+            .add_generator("gen", None, None, move || chunks.pop())
+            .unwrap();
+        assert!(!builder.is_retryable());
+        let (_, data) = builder.finish().unwrap();
+        let expected = "--B\r\nContent-Disposition: form-data; name=\"gen\"\r\n\
+            Content-Type: application/octet-stream\r\n\r\nabc\r\n--B--\r\n";
+        assert_eq!(String::from_utf8(data).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_retryable() {
+        let builder = MultipartBuilder::new()
+            .add_reader("r", None, None, io::empty())
+            .unwrap();
+        assert!(!builder.is_retryable());
+        let err = builder.into_retryable().unwrap_err();
+        let err = err
+            .into_inner()
+            .unwrap()
+            .downcast::<MultipartError>()
+            .unwrap();
+        assert_eq!(*err, MultipartError::OneShotPart { name: "r".into() });
+
+        let url = serve(|req, idx| match idx {
+            0 => response(503, &[], b"busy"),
+            _ => response(200, &[], &req.body),
+        });
+        let builder = MultipartBuilder::new()
+            .add_file("f", "test-vector0.txt")
+            .unwrap();
+        assert!(builder.is_retryable());
+        let body = builder.into_retryable().unwrap();
+        let resp = ureq::post(&url).send_multipart_retrying(&body, 2).unwrap();
+        assert_eq!(resp.status(), 200);
+        let mut expected = Vec::new();
+        body.reader().unwrap().read_to_end(&mut expected).unwrap();
+        assert_eq!(resp.into_string().unwrap().into_bytes(), expected);
+
+        // a single attempt gives up on the transient error
+        let url = serve(|_, _| response(503, &[], b"busy"));
+        let err = ureq::post(&url)
+            .send_multipart_retrying(&body, 1)
+            .unwrap_err();
+        assert!(matches!(err, ureq::Error::Status(503, _)));
+    }
+}
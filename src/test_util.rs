@@ -0,0 +1,97 @@
+//! helpers shared by the unit tests
+use std::io::prelude::*;
+use std::io::{BufRead, BufReader};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+/// a request received by the test server
+#[derive(Debug)]
+pub struct TestRequest {
+    pub head: String,
+    pub body: Vec<u8>,
+}
+
+impl TestRequest {
+    /// value of the first header called `name`, case insensitive
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.head.lines().skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case(name)
+                .then_some(value.trim())
+        })
+    }
+}
+
+/// build a raw http response
+pub fn response(status: u16, headers: &[(&str, &str)], body: &[u8]) -> Vec<u8> {
+    let mut out = format!(
+        "HTTP/1.1 {status} Test\r\nContent-Length: {}\r\n",
+        body.len()
+    );
+    for (name, value) in headers {
+        out.push_str(&format!("{name}: {value}\r\n"));
+    }
+    out.push_str("Connection: close\r\n\r\n");
+    let mut out = out.into_bytes();
+    out.extend_from_slice(body);
+    out
+}
+
+/// serve every incoming connection with `handler` on a local port
+///
+/// The handler gets the request and the zero based index of the connection.
+/// Returns the base url of the server, e.g. `http://127.0.0.1:1234`.
+pub fn serve<F>(handler: F) -> String
+where
+    F: Fn(&TestRequest, usize) -> Vec<u8> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for (idx, stream) in listener.incoming().enumerate() {
+            let mut stream = stream.unwrap();
+            if let Some(req) = read_request(&stream) {
+                let _ = stream.write_all(&handler(&req, idx));
+            }
+        }
+    });
+    format!("http://{addr}")
+}
+
+fn read_request(stream: &TcpStream) -> Option<TestRequest> {
+    let mut reader = BufReader::new(stream);
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        if line == "\r\n" {
+            break;
+        }
+        head.push_str(&line);
+    }
+    let mut req = TestRequest {
+        head,
+        body: Vec::new(),
+    };
+    if let Some(len) = req.header("Content-Length") {
+        let mut body = vec![0; len.parse().ok()?];
+        reader.read_exact(&mut body).ok()?;
+        req.body = body;
+    } else if req.header("Transfer-Encoding") == Some("chunked") {
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).ok()?;
+            let len = usize::from_str_radix(line.trim(), 16).ok()?;
+            let mut chunk = vec![0; len + 2];
+            reader.read_exact(&mut chunk).ok()?;
+            if len == 0 {
+                break;
+            }
+            req.body.extend_from_slice(&chunk[..len]);
+        }
+    }
+    Some(req)
+}